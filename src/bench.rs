@@ -0,0 +1,120 @@
+//! Замер времени этапов (`--bench`): захват, усреднение регионов, обработка цветов
+//! и запись в порт на настоящих настройках и оборудовании.
+
+use crate::config::AmbilightConfig;
+use crate::engine::Ambilight;
+use crate::format;
+use crate::output::OutputMode;
+use crate::shutdown;
+use crate::writer;
+use log::info;
+use scrap::Capturer;
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Времена одного этапа по кадрам.
+#[derive(Default)]
+pub struct StageTimes(Vec<Duration>);
+
+impl StageTimes {
+    pub fn record(&mut self, elapsed: Duration) {
+        self.0.push(elapsed);
+    }
+
+    /// Среднее и 99-й перцентиль; `None`, пока нет ни одного замера.
+    pub fn summary(&self) -> Option<(Duration, Duration)> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted = self.0.clone();
+        sorted.sort();
+        let average = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+        let p99 = sorted[(sorted.len() * 99).div_ceil(100) - 1];
+        Some((average, p99))
+    }
+}
+
+fn report(name: &str, times: &StageTimes) {
+    if let Some((average, p99)) = times.summary() {
+        info!(
+            "{:<22} среднее {:>7.2} мс, p99 {:>7.2} мс",
+            name,
+            average.as_secs_f64() * 1000.0,
+            p99.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+/// Обрабатывает `frames` кадров как основной цикл, но без паузы между кадрами, и выводит
+/// время каждого этапа. Захват — ожидание нового кадра; в остальные этапы оно не входит.
+/// Дополнительные дисплеи из `[[displays]]` не замеряются.
+pub fn run(config: &AmbilightConfig, capturer: &mut Capturer, frames: usize) -> Result<(), Box<dyn Error>> {
+    if config.output.mode != OutputMode::Serial {
+        return Err("--bench замеряет запись пакетов Adalight и работает только с mode = \"serial\"".into());
+    }
+    let (width, height) = (capturer.width(), capturer.height());
+    let mut engine = Ambilight::new(config.clone(), width, height)?;
+    let mut port = writer::open_output(config, engine.regions().len())?;
+    info!("Замер: {} кадров, {} светодиодов, {}x{}", frames, engine.regions().len(), width, height);
+
+    let mut capture = StageTimes::default();
+    let mut sampling = StageTimes::default();
+    let mut processing = StageTimes::default();
+    let mut write = StageTimes::default();
+    let mut converted_frame = Vec::new();
+    let mut measured = 0;
+    while measured < frames && !shutdown::stop_requested() {
+        let wait_start = Instant::now();
+        let frame = loop {
+            match capturer.frame() {
+                Ok(frame) => break frame,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(1)),
+                Err(e) => return Err(e.into()),
+            }
+        };
+        capture.record(wait_start.elapsed());
+
+        let start = Instant::now();
+        let (frame, _) = format::bgra_frame(&frame, config.pixel_format, width, height, &mut converted_frame)?;
+        let colors = engine.sample_frame(frame);
+        sampling.record(start.elapsed());
+
+        let start = Instant::now();
+        let colors = engine.process_colors(colors);
+        processing.record(start.elapsed());
+
+        let start = Instant::now();
+        port.write_all(&engine.build_packet(&colors))?;
+        port.flush()?;
+        write.record(start.elapsed());
+        measured += 1;
+    }
+
+    report("Захват (ожидание)", &capture);
+    report("Усреднение регионов", &sampling);
+    report("Обработка цветов", &processing);
+    report("Запись в порт", &write);
+    // Бенчмарк оставил бы ленту гореть последним кадром
+    port.write_all(&engine.build_packet(&vec![(0, 0, 0); engine.regions().len()]))?;
+    port.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_reports_average_and_p99() {
+        let mut times = StageTimes::default();
+        assert_eq!(times.summary(), None);
+        for ms in 1..=100 {
+            times.record(Duration::from_millis(ms));
+        }
+        let (average, p99) = times.summary().unwrap();
+        assert_eq!(average, Duration::from_micros(50_500));
+        assert_eq!(p99, Duration::from_millis(99));
+    }
+}
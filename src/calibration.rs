@@ -0,0 +1,105 @@
+use crate::color::{
+    BYTES_PER_PIXEL, CHANNEL_B, CHANNEL_R, average_region, color_temperature_to_rgb_multipliers,
+};
+use crate::regions::{LedRegion, MatrixWiring, create_matrix_regions};
+use log::info;
+use std::error::Error;
+
+/// Уровни серого полос эталонного изображения (слева направо), последняя — белая.
+const PATTERN_LEVELS: [u8; 8] = [32, 64, 96, 128, 160, 192, 224, 255];
+const PATTERN_WIDTH: u32 = 1280;
+const PATTERN_HEIGHT: u32 = 720;
+
+/// Сохраняет эталонное изображение: вертикальные полосы серого из `PATTERN_LEVELS`.
+pub fn write_pattern(path: &str) -> Result<(), Box<dyn Error>> {
+    let band_width = PATTERN_WIDTH / PATTERN_LEVELS.len() as u32;
+    let image = image::RgbImage::from_fn(PATTERN_WIDTH, PATTERN_HEIGHT, |x, _| {
+        let level = PATTERN_LEVELS[((x / band_width) as usize).min(PATTERN_LEVELS.len() - 1)];
+        image::Rgb([level, level, level])
+    });
+    image.save(path)?;
+    info!("Эталонное изображение сохранено в {}", path);
+    Ok(())
+}
+
+/// Центральные части полос эталона: края отбрасываются, чтобы не захватить соседние полосы.
+fn patch_regions(width: usize, height: usize) -> Vec<LedRegion> {
+    create_matrix_regions(PATTERN_LEVELS.len(), 1, MatrixWiring::Progressive, width, height)
+        .into_iter()
+        .map(|region| {
+            let margin_x = (region.x2 - region.x1) / 5;
+            let margin_y = (region.y2 - region.y1) * 3 / 10;
+            LedRegion {
+                x1: region.x1 + margin_x,
+                y1: region.y1 + margin_y,
+                x2: region.x2 - margin_x,
+                y2: region.y2 - margin_y,
+                edge: None,
+            }
+        })
+        .collect()
+}
+
+/// Сравнивает снимок эталонного изображения с ожидаемыми уровнями
+/// и выводит рекомендуемые `gamma` и `white_balance_temperature`.
+/// Снимок должен быть обрезан по границам эталона.
+pub fn run(path: &str) -> Result<(), Box<dyn Error>> {
+    let photo = image::open(path)?.to_rgba8();
+    let (width, height) = (photo.width() as usize, photo.height() as usize);
+
+    // average_region работает с кадром в порядке BGRA, как при захвате экрана
+    let mut frame = photo.into_raw();
+    for pixel in frame.chunks_exact_mut(BYTES_PER_PIXEL) {
+        pixel.swap(CHANNEL_R, CHANNEL_B);
+    }
+    let measured: Vec<(u8, u8, u8)> = patch_regions(width, height)
+        .iter()
+        .map(|region| average_region(&frame, width * BYTES_PER_PIXEL, region, 1).unwrap_or((0, 0, 0)))
+        .collect();
+    info!("Средние цвета полос: {:?}", measured);
+
+    let (white_r, white_g, white_b) = measured[measured.len() - 1];
+    if white_r == 0 || white_g == 0 || white_b == 0 {
+        return Err("Белая полоса на снимке слишком тёмная".into());
+    }
+
+    // Гамма: показатель степени, с которым измеренные уровни (относительно белого)
+    // зависят от ожидаемых
+    let mut gamma_sum = 0.0;
+    let mut gamma_count = 0;
+    for (&level, &(r, g, b)) in PATTERN_LEVELS.iter().zip(&measured).take(PATTERN_LEVELS.len() - 1) {
+        let expected = level as f32 / 255.0;
+        for (value, white) in [(r, white_r), (g, white_g), (b, white_b)] {
+            let relative = value as f32 / white as f32;
+            if relative > 0.0 && relative < 1.0 {
+                gamma_sum += relative.ln() / expected.ln();
+                gamma_count += 1;
+            }
+        }
+    }
+    if gamma_count == 0 {
+        return Err("Не удалось оценить гамму: полосы на снимке неразличимы".into());
+    }
+    let gamma = gamma_sum / gamma_count as f32;
+
+    // Баланс белого: множители, делающие белую полосу нейтральной,
+    // и ближайшая к ним цветовая температура
+    let inverse = [1.0 / white_r as f32, 1.0 / white_g as f32, 1.0 / white_b as f32];
+    let max = inverse[0].max(inverse[1]).max(inverse[2]);
+    let target = inverse.map(|m| m / max);
+    let temperature = (20..=240)
+        .map(|i| i as f32 * 50.0)
+        .min_by(|&a, &b| {
+            let error = |temp: f32| {
+                let (r, g, b) = color_temperature_to_rgb_multipliers(temp);
+                (r - target[0]).powi(2) + (g - target[1]).powi(2) + (b - target[2]).powi(2)
+            };
+            error(a).total_cmp(&error(b))
+        })
+        .unwrap();
+
+    println!("Рекомендуемые настройки:");
+    println!("gamma = {:.3}", gamma);
+    println!("white_balance_temperature = {:.1}", temperature);
+    Ok(())
+}
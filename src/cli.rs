@@ -0,0 +1,85 @@
+use ambilight::config::ConfigErrorPolicy;
+use crate::daemon::DaemonOptions;
+
+/// Что программа делает после запуска.
+pub enum Action {
+    /// Обычная работа: захват экрана и вывод на ленту
+    Run,
+    /// `--calibration-pattern <файл>`: сохранить эталонное изображение для калибровки
+    CalibrationPattern(String),
+    /// `--calibrate <снимок>`: подобрать gamma и баланс белого по снимку эталона
+    Calibrate(String),
+    /// `--export-svg <файл>`: сохранить схему раскладки и выйти
+    ExportSvg(String),
+    /// `--dump-regions <файл>`: захватить кадр, нарисовать на нём регионы и сохранить в PNG
+    DumpRegions(String),
+    /// `--bench <кадров>`: замерить время этапов обработки и выйти
+    Bench(usize),
+    /// `--test`: бегущая точка и заливки для проверки подключения и порядка светодиодов
+    SelfTest,
+}
+
+/// Аргументы командной строки.
+pub struct Cli {
+    pub action: Action,
+    /// `--config <файл>`: путь к настройкам, `.toml` или `.json` (по умолчанию config.toml)
+    pub config_path: String,
+    /// `--on-config-error fail|defaults|retry`
+    pub on_config_error: ConfigErrorPolicy,
+    /// `--daemon`: работа в фоне (на Windows — как служба); `--pid-file` и `--log-file`
+    /// без него службу не включают
+    pub daemon: Option<DaemonOptions>,
+    /// `--background`: без окна консоли, журнал — в файл из `--log-file`
+    pub background: bool,
+    /// `--log-file`: журнал для `--daemon` и `--background`
+    pub log_file: String,
+}
+
+/// Разбирает аргументы командной строки.
+pub fn parse() -> Result<Cli, String> {
+    let mut cli = Cli {
+        action: Action::Run,
+        config_path: "config.toml".to_string(),
+        on_config_error: ConfigErrorPolicy::Fail,
+        daemon: None,
+        background: false,
+        log_file: String::new(),
+    };
+    let mut daemon = false;
+    let mut options = DaemonOptions::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("После {} нужно значение", flag));
+        match flag.as_str() {
+            "--calibration-pattern" => cli.action = Action::CalibrationPattern(value()?),
+            "--calibrate" => cli.action = Action::Calibrate(value()?),
+            "--export-svg" => cli.action = Action::ExportSvg(value()?),
+            "--dump-regions" => cli.action = Action::DumpRegions(value()?),
+            "--test" => cli.action = Action::SelfTest,
+            "--bench" => {
+                let frames = value()?;
+                cli.action = match frames.parse() {
+                    Ok(frames) if frames > 0 => Action::Bench(frames),
+                    _ => return Err(format!("--bench: число кадров должно быть больше 0, задано {}", frames)),
+                };
+            }
+            "--config" => cli.config_path = value()?,
+            "--on-config-error" => {
+                cli.on_config_error = match value()?.as_str() {
+                    "fail" => ConfigErrorPolicy::Fail,
+                    "defaults" => ConfigErrorPolicy::Defaults,
+                    "retry" => ConfigErrorPolicy::Retry,
+                    other => return Err(format!("Неизвестное значение --on-config-error: {}", other)),
+                }
+            }
+            "--daemon" => daemon = true,
+            "--background" => cli.background = true,
+            "--pid-file" => options.pid_file = value()?,
+            "--log-file" => options.log_file = value()?,
+            _ => return Err(format!("Неизвестный аргумент: {}", flag)),
+        }
+    }
+    cli.log_file.clone_from(&options.log_file);
+    cli.daemon = daemon.then_some(options);
+    Ok(cli)
+}
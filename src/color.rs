@@ -0,0 +1,964 @@
+use crate::regions::{LedRegion, Side};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Каждый пиксель кадра занимает 4 байта (BGRA).
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Смещения каналов внутри пикселя. scrap отдаёт кадры в порядке B, G, R, A;
+/// альфа-канал в усреднении не участвует и обычно всегда равен 255.
+pub const CHANNEL_B: usize = 0;
+pub const CHANNEL_G: usize = 1;
+pub const CHANNEL_R: usize = 2;
+pub const CHANNEL_A: usize = 3;
+
+/// Вычисляет множители для коррекции белого по заданной цветовой температуре (в Кельвинах).
+#[allow(clippy::excessive_precision)]
+pub fn color_temperature_to_rgb_multipliers(temp: f32) -> (f32, f32, f32) {
+    let temp = temp / 100.0;
+    let (r, g, b): (f32, f32, f32);
+    if temp <= 66.0 {
+        r = 255.0;
+        g = 99.4708025861 * (temp.max(1.0)).ln() - 161.1195681661;
+        b = if temp <= 19.0 {
+            0.0
+        } else {
+            138.5177312231 * ((temp - 10.0).max(1.0)).ln() - 305.0447927307
+        };
+    } else {
+        r = 329.698727446 * ((temp - 60.0).max(1.0)).powf(-0.1332047592);
+        g = 288.1221695283 * ((temp - 60.0).max(1.0)).powf(-0.0755148492);
+        b = 255.0;
+    }
+    (r / 255.0, g / 255.0, b / 255.0)
+}
+
+
+/// Переводит значение sRGB (0.0–1.0) в линейный свет.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Переводит линейный свет (0.0–1.0) обратно в sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Таблица sRGB (0–255) → линейный свет (0.0–1.0) для усреднения в линейном свете.
+static SRGB_TO_LINEAR: LazyLock<[f32; 256]> =
+    LazyLock::new(|| std::array::from_fn(|value| srgb_to_linear(value as f32 / 255.0)));
+
+/// Сумма каналов пикселей с весами: в sRGB или, при `linear`, в линейном свете.
+struct ColorSum {
+    linear: bool,
+    r: f32,
+    g: f32,
+    b: f32,
+    weight: f32,
+}
+
+impl ColorSum {
+    fn new(linear: bool) -> Self {
+        ColorSum { linear, r: 0.0, g: 0.0, b: 0.0, weight: 0.0 }
+    }
+
+    fn add(&mut self, (r, g, b): (u8, u8, u8), weight: f32) {
+        let decode = |c: u8| if self.linear { SRGB_TO_LINEAR[c as usize] } else { c as f32 };
+        self.r += decode(r) * weight;
+        self.g += decode(g) * weight;
+        self.b += decode(b) * weight;
+        self.weight += weight;
+    }
+
+    fn average(&self) -> Option<(u8, u8, u8)> {
+        if self.weight == 0.0 {
+            return None;
+        }
+        let encode = |sum: f32| {
+            let value = sum / self.weight;
+            if self.linear { (linear_to_srgb(value) * 255.0).round() as u8 } else { value as u8 }
+        };
+        Some((encode(self.r), encode(self.g), encode(self.b)))
+    }
+}
+
+/// RGB → HSV: тон в градусах 0–360, насыщенность и яркость 0.0–1.0.
+pub fn rgb_to_hsv((r, g, b): (u8, u8, u8)) -> (f32, f32, f32) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+/// HSV → RGB, обратное к [`rgb_to_hsv`].
+pub fn hsv_to_rgb((hue, saturation, value): (f32, f32, f32)) -> (u8, u8, u8) {
+    let chroma = value * saturation;
+    let sector = (hue / 60.0).rem_euclid(6.0);
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let to_byte = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_byte(r), to_byte(g), to_byte(b))
+}
+
+/// Матрица коррекции цвета 3x3 (по строкам): `[r, g, b] = M * [r, g, b]`.
+pub type ColorMatrix = [[f32; 3]; 3];
+
+pub const IDENTITY_MATRIX: ColorMatrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Включённые этапы обработки цвета; выключение отдельных этапов помогает
+/// найти, какой из них портит цвета.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PipelineStages {
+    pub gamma: bool,
+    pub white_balance: bool,
+    pub brightness: bool,
+    pub smoothing: bool,
+}
+
+/// Параметры цветокоррекции, применяемые к среднему цвету региона.
+/// Порядок этапов: насыщенность, гамма, баланс белого, яркость, матрица; гамма и баланс
+/// белого меняются местами при [`Pipeline::set_white_balance_before_gamma`].
+/// Коррекция каждого канала зависит только от его значения 0–255, поэтому она
+/// заранее сводится в таблицы и при расчёте кадра не вызывает `powf`.
+pub struct Pipeline {
+    /// Показатели гаммы для R, G и B
+    gamma: (f32, f32, f32),
+    /// Множитель насыщенности (в HSV), 1.0 — без изменений
+    saturation: f32,
+    /// Множители баланса белого для R, G и B
+    white_balance: (f32, f32, f32),
+    /// Баланс белого до гаммы, а не после
+    white_balance_before_gamma: bool,
+    /// Матрица коррекции после баланса белого; None — единичная
+    color_matrix: Option<ColorMatrix>,
+    /// Каналы, вышедшие за 255, уменьшаются вместе, а не обрезаются по отдельности
+    soft_clip: bool,
+    /// Множитель яркости 0.0–1.0
+    brightness: f32,
+    stages: PipelineStages,
+    /// Таблицы R, G, B: среднее значение канала → значение для светодиода
+    /// в формате 8.8 (старший байт — целая часть, младший — дробная для дизеринга).
+    /// Значения больше 255 не обрезаются: это делает [`Pipeline::clip`]
+    lut: [[u32; 256]; 3],
+}
+
+impl Pipeline {
+    pub fn new(
+        gamma: (f32, f32, f32),
+        saturation: f32,
+        white_balance_temperature: f32,
+        brightness: f32,
+        stages: PipelineStages,
+    ) -> Self {
+        let white_balance = color_temperature_to_rgb_multipliers(white_balance_temperature);
+        let mut pipeline = Pipeline {
+            gamma,
+            saturation,
+            white_balance,
+            white_balance_before_gamma: false,
+            color_matrix: None,
+            soft_clip: false,
+            brightness,
+            stages,
+            lut: [[0; 256]; 3],
+        };
+        pipeline.rebuild_lut();
+        pipeline
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    pub fn stages(&self) -> PipelineStages {
+        self.stages
+    }
+
+    pub fn set_gamma(&mut self, gamma: (f32, f32, f32)) {
+        if gamma != self.gamma {
+            self.gamma = gamma;
+            self.rebuild_lut();
+        }
+    }
+
+    /// Пересчитывает множители баланса белого для новой цветовой температуры.
+    pub fn set_white_balance(&mut self, white_balance_temperature: f32) {
+        let white_balance = color_temperature_to_rgb_multipliers(white_balance_temperature);
+        if white_balance != self.white_balance {
+            self.white_balance = white_balance;
+            self.rebuild_lut();
+        }
+    }
+
+    /// Баланс белого до гаммы: множители применяются к исходной яркости канала,
+    /// и гамма затем искажает их вместе с ней.
+    pub fn set_white_balance_before_gamma(&mut self, before: bool) {
+        if before != self.white_balance_before_gamma {
+            self.white_balance_before_gamma = before;
+            self.rebuild_lut();
+        }
+    }
+
+    /// Матрица смешивает каналы, поэтому в таблицы не входит и применяется к их результату.
+    /// Яркость — общий множитель, так что порядок с ней не важен.
+    pub fn set_color_matrix(&mut self, matrix: ColorMatrix) {
+        self.color_matrix = (matrix != IDENTITY_MATRIX).then_some(matrix);
+    }
+
+    pub fn set_soft_clip(&mut self, soft_clip: bool) {
+        self.soft_clip = soft_clip;
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        if brightness != self.brightness {
+            self.brightness = brightness;
+            self.rebuild_lut();
+        }
+    }
+
+    pub fn set_stages(&mut self, stages: PipelineStages) {
+        if stages != self.stages {
+            self.stages = stages;
+            self.rebuild_lut();
+        }
+    }
+
+    fn rebuild_lut(&mut self) {
+        let (gamma_r, gamma_g, gamma_b) = self.gamma;
+        let (r_mult, g_mult, b_mult) = self.white_balance;
+        let channels = [(gamma_r, r_mult), (gamma_g, g_mult), (gamma_b, b_mult)];
+        let mut lut = [[0; 256]; 3];
+        for (table, (gamma, mult)) in lut.iter_mut().zip(channels) {
+            for (value, entry) in table.iter_mut().enumerate() {
+                *entry = self.correct_channel(value as f32, gamma, mult);
+            }
+        }
+        self.lut = lut;
+    }
+
+    /// Гамма, баланс белого и яркость для одного канала, в формате 8.8.
+    fn correct_channel(&self, mut value: f32, gamma: f32, white_balance: f32) -> u32 {
+        let balance = self.stages.white_balance;
+        if balance && self.white_balance_before_gamma {
+            value *= white_balance;
+        }
+
+        // Применяем гамма-коррекцию
+        if self.stages.gamma {
+            value = 255.0 * ((value / 255.0).powf(gamma));
+        }
+
+        // Применяем баланс белого
+        if balance && !self.white_balance_before_gamma {
+            value *= white_balance;
+        }
+
+        // Применяем яркость
+        if self.stages.brightness {
+            value *= self.brightness;
+        }
+
+        (value.max(0.0) * 256.0) as u32
+    }
+
+    /// Приводит каналы 8.8 к 0–255. Обычно каждый канал обрезается отдельно, и яркий цвет,
+    /// у которого переполнился один канал, сдвигается по оттенку. С `soft_clip` все каналы
+    /// уменьшаются в одно и то же число раз: оттенок сохраняется, цвет лишь темнее.
+    fn clip(&self, values: [u32; 3]) -> [u16; 3] {
+        const MAX: u32 = 255 * 256;
+        let peak = values.into_iter().max().unwrap_or(0);
+        if self.soft_clip && peak > MAX {
+            values.map(|value| (value as u64 * MAX as u64 / peak as u64) as u16)
+        } else {
+            values.map(|value| value.min(MAX) as u16)
+        }
+    }
+
+    /// Скорректированные каналы в формате 8.8.
+    fn apply_precise(&self, average: (u8, u8, u8)) -> [u16; 3] {
+        // Усреднение размывает цвета, поэтому насыщенность поднимается до остальной коррекции
+        let (r, g, b) = if self.saturation != 1.0 {
+            let (hue, saturation, value) = rgb_to_hsv(average);
+            hsv_to_rgb((hue, (saturation * self.saturation).min(1.0), value))
+        } else {
+            average
+        };
+        let corrected = [self.lut[0][r as usize], self.lut[1][g as usize], self.lut[2][b as usize]];
+        let mixed = match self.color_matrix {
+            Some(matrix) => matrix.map(|row| {
+                let value: f32 = row.iter().zip(corrected).map(|(k, c)| k * c as f32).sum();
+                value.max(0.0) as u32
+            }),
+            None => corrected,
+        };
+        self.clip(mixed)
+    }
+
+    /// Преобразует средний цвет региона в цвет для светодиода.
+    pub fn apply(&self, average: (u8, u8, u8)) -> (u8, u8, u8) {
+        let [r, g, b] = self.apply_precise(average).map(|value| (value >> 8) as u8);
+        (r, g, b)
+    }
+
+    /// Как [`Pipeline::apply`], но дробная часть не отбрасывается, а копится в `residual`
+    /// от кадра к кадру (временной дизеринг): в среднем по времени светодиод показывает
+    /// точное значение, и на малой яркости не видно ступенек.
+    pub fn apply_dithered(&self, average: (u8, u8, u8), residual: &mut [u8; 3]) -> (u8, u8, u8) {
+        let mut out = [0u8; 3];
+        for ((value, residual), out) in self.apply_precise(average).into_iter().zip(residual).zip(&mut out) {
+            let value = value as u32 + *residual as u32;
+            *out = (value >> 8).min(255) as u8;
+            *residual = (value & 0xFF) as u8;
+        }
+        (out[0], out[1], out[2])
+    }
+}
+
+/// Чем становится цвет региона.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    /// Среднее пикселей региона
+    #[default]
+    Average,
+    /// Самый частый цвет региона (см. [`dominant_region`])
+    Dominant,
+}
+
+/// Как выбираются пиксели региона для расчёта его цвета.
+#[derive(Debug, Clone, Copy)]
+pub struct Sampling {
+    /// Берётся каждый `step`-й пиксель по x и по y (1 — все пиксели)
+    pub step: usize,
+    /// Процент отбрасываемых самых тёмных и самых ярких пикселей (0 — обычное среднее)
+    pub trim_percent: usize,
+    /// Крутизна спада веса пикселя от края экрана вглубь (0 — все пиксели равноценны)
+    pub edge_falloff: f32,
+    /// Усреднять в линейном свете, а не в sRGB
+    pub linear: bool,
+    /// Брать самый частый цвет региона вместо среднего
+    pub dominant: bool,
+}
+
+impl Sampling {
+    /// Обычное среднее по всем пикселям: только его можно считать по таблице сумм.
+    fn is_plain(&self) -> bool {
+        self.step <= 1 && self.trim_percent == 0 && self.edge_falloff == 0.0 && !self.linear && !self.dominant
+    }
+}
+
+/// Таблица сумм (интегральное изображение) каналов R, G, B: в ячейке `(x, y)`
+/// лежит сумма всех пикселей левее и выше неё. Строится один раз за кадр, после чего
+/// сумма любого прямоугольника находится по четырём ячейкам, как бы толст ни был регион.
+///
+/// Суммы хранятся в `u32` и складываются с переполнением: на больших экранах ячейки
+/// переполняются, но разность четырёх ячеек остаётся точной, пока сумма самого
+/// региона меньше 2^32.
+#[derive(Default)]
+pub struct SummedAreaTable {
+    /// Ширина таблицы: на одну ячейку больше ширины кадра
+    width: usize,
+    sums: Vec<[u32; 3]>,
+}
+
+impl SummedAreaTable {
+    /// Строит таблицу по левому верхнему углу кадра размером `width` x `height`.
+    pub fn build(&mut self, frame: &[u8], stride: usize, width: usize, height: usize) {
+        self.width = width + 1;
+        self.sums.clear();
+        self.sums.resize(self.width * (height + 1), [0; 3]);
+        for y in 0..height {
+            let row = &frame[y * stride..y * stride + width * BYTES_PER_PIXEL];
+            let mut row_sum = [0u32; 3];
+            for (x, pixel) in row.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+                for (sum, channel) in row_sum.iter_mut().zip([CHANNEL_R, CHANNEL_G, CHANNEL_B]) {
+                    *sum = sum.wrapping_add(pixel[channel] as u32);
+                }
+                let above = self.sums[y * self.width + x + 1];
+                self.sums[(y + 1) * self.width + x + 1] =
+                    [0, 1, 2].map(|channel| above[channel].wrapping_add(row_sum[channel]));
+            }
+        }
+    }
+
+    /// Средний цвет региона по таблице; совпадает с [`average_region`] при `step = 1`.
+    pub fn average_region(&self, region: &LedRegion) -> Option<(u8, u8, u8)> {
+        let count = ((region.x2 - region.x1) * (region.y2 - region.y1)) as u32;
+        if count == 0 {
+            return None;
+        }
+        let at = |x: usize, y: usize| self.sums[y * self.width + x];
+        let (bottom_right, top_right) = (at(region.x2, region.y2), at(region.x2, region.y1));
+        let (bottom_left, top_left) = (at(region.x1, region.y2), at(region.x1, region.y1));
+        let [r, g, b] = [0, 1, 2].map(|channel| {
+            let sum = bottom_right[channel]
+                .wrapping_sub(top_right[channel])
+                .wrapping_sub(bottom_left[channel])
+                .wrapping_add(top_left[channel]);
+            (sum / count) as u8
+        });
+        Some((r, g, b))
+    }
+}
+
+/// Помещаются ли регионы в кадр длиной `frame_len` со строками по `stride` байт:
+/// каждый регион должен лежать внутри строки и заканчиваться не дальше конца кадра.
+/// У захвата с выровненными строками `stride` больше `width * 4`, а у последней строки
+/// выравнивания может не быть, поэтому кадр короче `stride * height`.
+pub fn regions_fit(frame_len: usize, stride: usize, regions: &[LedRegion]) -> bool {
+    regions.iter().all(|region| {
+        region.x1 >= region.x2
+            || region.y1 >= region.y2
+            || (region.x2 * BYTES_PER_PIXEL <= stride
+                && (region.y2 - 1) * stride + region.x2 * BYTES_PER_PIXEL <= frame_len)
+    })
+}
+
+/// Средний цвет (R, G, B) прямоугольника кадра по каждому `step`-му пикселю
+/// по x и по y; первый пиксель региона берётся всегда.
+/// `stride` — длина строки кадра в байтах (может быть больше `width * 4` из-за выравнивания).
+pub fn average_region(frame: &[u8], stride: usize, region: &LedRegion, step: usize) -> Option<(u8, u8, u8)> {
+    let step = step.max(1);
+    let mut sum_r: u64 = 0;
+    let mut sum_g: u64 = 0;
+    let mut sum_b: u64 = 0;
+    let mut count: u64 = 0;
+    for y in (region.y1..region.y2).step_by(step) {
+        let row_base = y * stride;
+        let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            sum_b += pixel[CHANNEL_B] as u64;
+            sum_g += pixel[CHANNEL_G] as u64;
+            sum_r += pixel[CHANNEL_R] as u64;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return None;
+    }
+    Some(((sum_r / count) as u8, (sum_g / count) as u8, (sum_b / count) as u8))
+}
+
+/// Среднее как в [`average_region`], но в линейном свете: каждый пиксель переводится
+/// из sRGB в линейный свет, усредняется и переводится обратно. Регион из светлых и
+/// тёмных пикселей так не получается темнее, чем выглядит на экране.
+pub fn linear_average_region(frame: &[u8], stride: usize, region: &LedRegion, step: usize) -> Option<(u8, u8, u8)> {
+    let step = step.max(1);
+    let mut sum = ColorSum::new(true);
+    for y in (region.y1..region.y2).step_by(step) {
+        let row_base = y * stride;
+        let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            sum.add((pixel[CHANNEL_R], pixel[CHANNEL_G], pixel[CHANNEL_B]), 1.0);
+        }
+    }
+    sum.average()
+}
+
+/// Средний цвет региона, в котором пиксель на глубине `d` от края экрана весит
+/// `exp(-falloff * d / толщина)`: внутренние пиксели толстого региона меньше
+/// размывают цвет края. Пиксели выбираются так же, как в [`average_region`].
+pub fn edge_weighted_average_region(
+    frame: &[u8],
+    stride: usize,
+    region: &LedRegion,
+    edge: Side,
+    step: usize,
+    falloff: f32,
+    linear: bool,
+) -> Option<(u8, u8, u8)> {
+    let step = step.max(1);
+    let (width, height) = (region.x2 - region.x1, region.y2 - region.y1);
+    let thickness = match edge {
+        Side::Top | Side::Bottom => height,
+        Side::Left | Side::Right => width,
+    };
+    // Вес по глубине: индекс 0 — пиксели у самого края экрана
+    let weights: Vec<f32> = (0..thickness)
+        .map(|depth| (-falloff * depth as f32 / thickness as f32).exp())
+        .collect();
+
+    let mut sum = ColorSum::new(linear);
+    for dy in (0..height).step_by(step) {
+        let row_base = (region.y1 + dy) * stride;
+        let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+        for (dx, pixel) in row.chunks_exact(BYTES_PER_PIXEL).enumerate().step_by(step) {
+            let weight = weights[match edge {
+                Side::Top => dy,
+                Side::Bottom => height - 1 - dy,
+                Side::Left => dx,
+                Side::Right => width - 1 - dx,
+            }];
+            sum.add((pixel[CHANNEL_R], pixel[CHANNEL_G], pixel[CHANNEL_B]), weight);
+        }
+    }
+    sum.average()
+}
+
+/// Каналы квантуются до 4 бит: 4096 корзин гистограммы.
+const DOMINANT_BUCKET_SHIFT: u32 = 4;
+
+/// Самый частый цвет региона: пиксели раскладываются по корзинам гистограммы
+/// (4 бита на канал), и берётся среднее пикселей самой населённой корзины.
+/// В отличие от среднего, яркий объект на тёмном фоне не превращается в грязную смесь.
+/// Пиксели выбираются так же, как в [`average_region`].
+pub fn dominant_region(frame: &[u8], stride: usize, region: &LedRegion, step: usize) -> Option<(u8, u8, u8)> {
+    let step = step.max(1);
+    let bits = 8 - DOMINANT_BUCKET_SHIFT;
+    // Число пикселей и суммы каналов в каждой корзине
+    let mut buckets = vec![(0u32, [0u64; 3]); 1 << (3 * bits)];
+    for y in (region.y1..region.y2).step_by(step) {
+        let row_base = y * stride;
+        let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            let [r, g, b] = [pixel[CHANNEL_R], pixel[CHANNEL_G], pixel[CHANNEL_B]];
+            let index = ((r as usize >> DOMINANT_BUCKET_SHIFT) << (2 * bits))
+                | ((g as usize >> DOMINANT_BUCKET_SHIFT) << bits)
+                | (b as usize >> DOMINANT_BUCKET_SHIFT);
+            let (count, sums) = &mut buckets[index];
+            *count += 1;
+            for (sum, value) in sums.iter_mut().zip([r, g, b]) {
+                *sum += value as u64;
+            }
+        }
+    }
+
+    // При равенстве берётся первая корзина, чтобы цвет не прыгал между кадрами
+    let (count, sums) = buckets.iter().rev().max_by_key(|(count, _)| *count)?;
+    if *count == 0 {
+        return None;
+    }
+    let [r, g, b] = sums.map(|sum| (sum / *count as u64) as u8);
+    Some((r, g, b))
+}
+
+/// Усечённое среднее: отбрасывает `trim_percent` процентов самых тёмных и столько же
+/// самых ярких пикселей региона и усредняет остальные. Несколько ярких пикселей
+/// (курсор, субтитры) так почти не влияют на цвет региона.
+/// Пиксели выбираются так же, как в [`average_region`].
+pub fn trimmed_average_region(
+    frame: &[u8],
+    stride: usize,
+    region: &LedRegion,
+    step: usize,
+    trim_percent: usize,
+    linear: bool,
+) -> Option<(u8, u8, u8)> {
+    let step = step.max(1);
+    let mut pixels = Vec::with_capacity((region.x2 - region.x1) * (region.y2 - region.y1) / (step * step));
+    for y in (region.y1..region.y2).step_by(step) {
+        let row_base = y * stride;
+        let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            pixels.push((pixel[CHANNEL_R], pixel[CHANNEL_G], pixel[CHANNEL_B]));
+        }
+    }
+
+    // Яркость по Rec. 709 в целых числах (коэффициенты в сумме дают 256)
+    pixels.sort_unstable_by_key(|&(r, g, b)| 54 * r as u32 + 183 * g as u32 + 19 * b as u32);
+    let trim = pixels.len() * trim_percent.min(49) / 100;
+    let mut sum = ColorSum::new(linear);
+    for &pixel in &pixels[trim..pixels.len() - trim] {
+        sum.add(pixel, 1.0);
+    }
+    sum.average()
+}
+
+/// Поднимает слишком тёмные светодиоды до `min_brightness` процентов: самый яркий канал
+/// доводится до порога, остальные масштабируются вместе с ним, поэтому оттенок сохраняется.
+/// Полностью чёрный светодиод становится тускло-белым.
+pub fn apply_min_brightness(colors: &mut [(u8, u8, u8)], min_brightness: usize) {
+    let floor = (min_brightness.min(100) * 255 / 100) as u8;
+    if floor == 0 {
+        return;
+    }
+    for (r, g, b) in colors.iter_mut() {
+        let max = (*r).max(*g).max(*b);
+        if max >= floor {
+            continue;
+        }
+        if max == 0 {
+            (*r, *g, *b) = (floor, floor, floor);
+            continue;
+        }
+        let scale = floor as f32 / max as f32;
+        let lift = |c: u8| (c as f32 * scale).round().min(255.0) as u8;
+        (*r, *g, *b) = (lift(*r), lift(*g), lift(*b));
+    }
+}
+
+/// Фильтр итоговых цветов ленты.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFilter {
+    #[default]
+    None,
+    /// Только красный канал с яркостью исходного цвета: не слепит в тёмном зале
+    RedOnly,
+    /// Оттенки серого той же яркости
+    Grayscale,
+}
+
+/// Яркость цвета по Rec. 709.
+pub fn luma((r, g, b): (u8, u8, u8)) -> f32 {
+    0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32
+}
+
+/// Переводит цвета в оттенки красного или серого, сохраняя их яркость.
+pub fn apply_output_filter(colors: &mut [(u8, u8, u8)], filter: OutputFilter) {
+    if filter == OutputFilter::None {
+        return;
+    }
+    for color in colors.iter_mut() {
+        let value = luma(*color).round().min(255.0) as u8;
+        *color = match filter {
+            OutputFilter::RedOnly => (value, 0, 0),
+            _ => (value, value, value),
+        };
+    }
+}
+
+/// Умножает цвет каждого светодиода на множитель яркости его стороны.
+/// Регионы без стороны (углы, матрица, импорт) не меняются.
+pub fn apply_side_brightness(colors: &mut [(u8, u8, u8)], regions: &[LedRegion], multiplier: impl Fn(Side) -> f32) {
+    for ((r, g, b), region) in colors.iter_mut().zip(regions) {
+        let Some(side) = region.edge else { continue };
+        let multiplier = multiplier(side);
+        if multiplier == 1.0 {
+            continue;
+        }
+        let scale = |c: u8| (c as f32 * multiplier).round().min(255.0) as u8;
+        (*r, *g, *b) = (scale(*r), scale(*g), scale(*b));
+    }
+}
+
+/// Проверяет, меняется ли альфа-канал под регионами. Для захвата экрана он постоянен,
+/// поэтому меняющиеся значения говорят о другом формате кадра и неверных цветах.
+pub fn alpha_varies(frame: &[u8], stride: usize, regions: &[LedRegion]) -> bool {
+    let mut expected = None;
+    for region in regions {
+        for y in region.y1..region.y2 {
+            let row_base = y * stride;
+            let row = &frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL];
+            for pixel in row.chunks_exact(BYTES_PER_PIXEL) {
+                let alpha = pixel[CHANNEL_A];
+                match expected {
+                    None => expected = Some(alpha),
+                    Some(value) if value != alpha => return true,
+                    _ => {}
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Копирует в `out` байты всех регионов кадра подряд, чтобы дешево сравнивать кадры между собой.
+pub fn snapshot_regions(frame: &[u8], stride: usize, regions: &[LedRegion], out: &mut Vec<u8>) {
+    out.clear();
+    for region in regions {
+        for y in region.y1..region.y2 {
+            let row_base = y * stride;
+            out.extend_from_slice(&frame[row_base + region.x1 * BYTES_PER_PIXEL..row_base + region.x2 * BYTES_PER_PIXEL]);
+        }
+    }
+}
+
+/// Параллельно рассчитывает цвет каждого светодиода по кадру.
+/// При `sampling.trim_percent > 0` вместо обычного среднего берётся усечённое.
+/// С `dither` цвета дизерингуются, в нём хранятся накопленные остатки каждого светодиода.
+/// С `table` обычное среднее считается по таблице сумм, которая перестраивается по кадру;
+/// прореживание, усечение, веса и линейный свет по-прежнему идут попиксельно.
+pub fn compute_colors(
+    frame: &[u8],
+    stride: usize,
+    regions: &[LedRegion],
+    sampling: Sampling,
+    pipeline: &Pipeline,
+    dither: Option<&mut Vec<[u8; 3]>>,
+    table: Option<&mut SummedAreaTable>,
+) -> Vec<(u8, u8, u8)> {
+    debug_assert!(regions_fit(frame.len(), stride, regions), "регионы выходят за кадр");
+    let table = match table {
+        Some(table) if sampling.is_plain() => {
+            let width = regions.iter().map(|region| region.x2).max().unwrap_or(0);
+            let height = regions.iter().map(|region| region.y2).max().unwrap_or(0);
+            table.build(frame, stride, width, height);
+            Some(&*table)
+        }
+        _ => None,
+    };
+    let averages = regions.par_iter().map(|region| match region.edge {
+        _ if let Some(table) = table => table.average_region(region),
+        _ if sampling.dominant => dominant_region(frame, stride, region, sampling.step),
+        _ if sampling.trim_percent > 0 => {
+            trimmed_average_region(frame, stride, region, sampling.step, sampling.trim_percent, sampling.linear)
+        }
+        Some(edge) if sampling.edge_falloff > 0.0 => edge_weighted_average_region(
+            frame,
+            stride,
+            region,
+            edge,
+            sampling.step,
+            sampling.edge_falloff,
+            sampling.linear,
+        ),
+        _ if sampling.linear => linear_average_region(frame, stride, region, sampling.step),
+        _ => average_region(frame, stride, region, sampling.step),
+    });
+    match dither {
+        Some(residuals) => {
+            residuals.resize(regions.len(), [0; 3]);
+            averages
+                .zip(residuals.par_iter_mut())
+                .map(|(average, residual)| match average {
+                    Some(avg) => pipeline.apply_dithered(avg, residual),
+                    None => (0, 0, 0),
+                })
+                .collect()
+        }
+        None => averages
+            .map(|average| match average {
+                Some(avg) => pipeline.apply(avg),
+                None => (0, 0, 0),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsv_round_trip() {
+        for color in [(0, 0, 0), (255, 255, 255), (255, 0, 0), (12, 200, 90), (30, 60, 250), (128, 128, 0)] {
+            assert_eq!(hsv_to_rgb(rgb_to_hsv(color)), color);
+        }
+        assert_eq!(rgb_to_hsv((0, 0, 255)), (240.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn lookup_table_matches_float_formula() {
+        let stages = PipelineStages { gamma: true, white_balance: true, brightness: true, smoothing: true };
+        let pipeline = Pipeline::new((2.2, 1.8, 1.6), 1.0, 4500.0, 0.8, stages);
+        let (r_mult, g_mult, b_mult) = color_temperature_to_rgb_multipliers(4500.0);
+
+        for value in [0u8, 1, 17, 64, 128, 200, 254, 255] {
+            let expected = |gamma: f32, mult: f32| {
+                (255.0 * (value as f32 / 255.0).powf(gamma) * mult * 0.8).min(255.0)
+            };
+            let (r, g, b) = pipeline.apply((value, value, value));
+            assert!((r as f32 - expected(2.2, r_mult)).abs() < 1.0);
+            assert!((g as f32 - expected(1.8, g_mult)).abs() < 1.0);
+            assert!((b as f32 - expected(1.6, b_mult)).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn white_balance_can_precede_gamma() {
+        let stages = PipelineStages { gamma: true, white_balance: true, brightness: false, smoothing: false };
+        let mut pipeline = Pipeline::new((2.0, 2.0, 2.0), 1.0, 4500.0, 1.0, stages);
+        let (r_mult, _, b_mult) = color_temperature_to_rgb_multipliers(4500.0);
+        let gamma = |value: f32| 255.0 * (value / 255.0).powi(2);
+
+        let (r, _, b) = pipeline.apply((128, 128, 128));
+        assert!((r as f32 - gamma(128.0) * r_mult).abs() < 1.0);
+        assert!((b as f32 - gamma(128.0) * b_mult).abs() < 1.0);
+
+        pipeline.set_white_balance_before_gamma(true);
+        let (r, _, b) = pipeline.apply((128, 128, 128));
+        assert!((r as f32 - gamma(128.0 * r_mult)).abs() < 1.0);
+        assert!((b as f32 - gamma(128.0 * b_mult)).abs() < 1.0);
+    }
+
+    #[test]
+    fn trimmed_mean_ignores_bright_outliers() {
+        // Регион 10x10 серого цвета, в котором 5 пикселей белые (например, курсор)
+        let mut frame = [50u8, 50, 50, 255].repeat(100);
+        for pixel in frame.chunks_exact_mut(BYTES_PER_PIXEL).step_by(20) {
+            pixel[..3].copy_from_slice(&[255, 255, 255]);
+        }
+        let region = LedRegion { x1: 0, y1: 0, x2: 10, y2: 10, edge: None };
+        let stride = 10 * BYTES_PER_PIXEL;
+
+        let (mean, _, _) = average_region(&frame, stride, &region, 1).unwrap();
+        assert!(mean > 55);
+        assert_eq!(trimmed_average_region(&frame, stride, &region, 1, 10, false), Some((50, 50, 50)));
+    }
+
+    #[test]
+    fn sample_step_takes_every_nth_pixel() {
+        // Строка 4x1: пиксели с красным 0, 40, 200, 100
+        let frame = [0, 0, 0, 255, 0, 0, 40, 255, 0, 0, 200, 255, 0, 0, 100, 255];
+        let region = LedRegion { x1: 0, y1: 0, x2: 4, y2: 1, edge: None };
+        assert_eq!(average_region(&frame, 16, &region, 2), Some((100, 0, 0)));
+        // Шаг больше региона и нулевой шаг всё равно дают хотя бы один пиксель
+        assert_eq!(average_region(&frame, 16, &region, 10), Some((0, 0, 0)));
+        assert_eq!(average_region(&frame, 16, &region, 0), Some((85, 0, 0)));
+    }
+
+    #[test]
+    fn edge_weighting_favours_pixels_near_the_edge() {
+        // Регион верхней стороны 1x4: у края красный 200, глубже — 0
+        let frame = [0, 0, 200, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let region = LedRegion { x1: 0, y1: 0, x2: 1, y2: 4, edge: Some(Side::Top) };
+        assert_eq!(average_region(&frame, 4, &region, 1), Some((50, 0, 0)));
+        let (r, _, _) = edge_weighted_average_region(&frame, 4, &region, Side::Top, 1, 2.0, false).unwrap();
+        assert!(r > 80);
+        // Со стороны нижнего края тот же пиксель оказывается самым глубоким
+        let (r, _, _) = edge_weighted_average_region(&frame, 4, &region, Side::Bottom, 1, 2.0, false).unwrap();
+        assert!(r < 50);
+        // Нулевая крутизна — обычное среднее
+        assert_eq!(edge_weighted_average_region(&frame, 4, &region, Side::Top, 1, 0.0, false), Some((50, 0, 0)));
+    }
+
+    #[test]
+    fn min_brightness_keeps_hue() {
+        let mut colors = [(10, 5, 0), (0, 0, 0), (200, 0, 0)];
+        apply_min_brightness(&mut colors, 10);
+        // Порог 25: тёмный оранжевый поднимается целиком, яркий не меняется
+        assert_eq!(colors, [(25, 13, 0), (25, 25, 25), (200, 0, 0)]);
+    }
+
+    #[test]
+    fn side_brightness_scales_only_its_side() {
+        let regions = [
+            LedRegion { x1: 0, y1: 0, x2: 1, y2: 1, edge: Some(Side::Bottom) },
+            LedRegion { x1: 0, y1: 0, x2: 1, y2: 1, edge: Some(Side::Top) },
+            LedRegion { x1: 0, y1: 0, x2: 1, y2: 1, edge: None },
+        ];
+        let mut colors = [(200, 100, 51), (200, 100, 51), (200, 100, 51)];
+        apply_side_brightness(&mut colors, &regions, |side| if side == Side::Bottom { 0.5 } else { 1.0 });
+        assert_eq!(colors, [(100, 50, 26), (200, 100, 51), (200, 100, 51)]);
+    }
+
+    #[test]
+    fn dithering_averages_to_the_exact_value() {
+        // Яркость 0.3 переводит 10 в 3.0, а 255 — в 76.5: половина кадров 76, половина 77
+        let stages = PipelineStages { gamma: false, white_balance: false, brightness: true, smoothing: false };
+        let pipeline = Pipeline::new((1.0, 1.0, 1.0), 1.0, 6600.0, 0.3, stages);
+        let mut residual = [0; 3];
+        let sum: u32 = (0..100).map(|_| pipeline.apply_dithered((255, 0, 10), &mut residual).0 as u32).sum();
+        assert_eq!(sum, 7650);
+        assert_eq!(pipeline.apply((255, 0, 10)), (76, 0, 3));
+    }
+
+    #[test]
+    fn linear_average_of_black_and_white_is_brighter() {
+        let frame = [0, 0, 0, 255, 255, 255, 255, 255];
+        let region = LedRegion { x1: 0, y1: 0, x2: 2, y2: 1, edge: None };
+        assert_eq!(average_region(&frame, 8, &region, 1), Some((127, 127, 127)));
+        // Половина линейного света — это около 188 в sRGB
+        assert_eq!(linear_average_region(&frame, 8, &region, 1), Some((188, 188, 188)));
+        // Один пиксель переводится туда и обратно без потерь
+        let pixel = LedRegion { x1: 0, y1: 0, x2: 1, y2: 1, edge: None };
+        assert_eq!(linear_average_region(&[40, 90, 200, 255], 4, &pixel, 1), Some((200, 90, 40)));
+    }
+
+    #[test]
+    fn summed_area_table_matches_pixel_average() {
+        // Кадр 7x5 с выравниванием строки до 8 пикселей и разными каналами
+        let stride = 8 * BYTES_PER_PIXEL;
+        let frame: Vec<u8> = (0..stride * 5).map(|i| (i * 37 % 251) as u8).collect();
+        let mut table = SummedAreaTable::default();
+        table.build(&frame, stride, 7, 5);
+        for region in [
+            LedRegion { x1: 0, y1: 0, x2: 7, y2: 5, edge: None },
+            LedRegion { x1: 2, y1: 1, x2: 5, y2: 4, edge: Some(Side::Top) },
+            LedRegion { x1: 6, y1: 4, x2: 7, y2: 5, edge: None },
+        ] {
+            assert_eq!(table.average_region(&region), average_region(&frame, stride, &region, 1));
+        }
+        assert_eq!(table.average_region(&LedRegion { x1: 3, y1: 2, x2: 3, y2: 4, edge: None }), None);
+    }
+
+    #[test]
+    fn dominant_color_ignores_minority_pixels() {
+        // 6 красных пикселей и 4 синих: среднее — смесь, доминирующий — красный
+        let mut frame = [0, 0, 200, 255].repeat(6);
+        frame.extend([200, 0, 0, 255].repeat(4));
+        let region = LedRegion { x1: 0, y1: 0, x2: 10, y2: 1, edge: None };
+        assert_eq!(average_region(&frame, 40, &region, 1), Some((120, 0, 80)));
+        assert_eq!(dominant_region(&frame, 40, &region, 1), Some((200, 0, 0)));
+        let empty = LedRegion { x1: 3, y1: 0, x2: 3, y2: 1, edge: None };
+        assert_eq!(dominant_region(&frame, 40, &empty, 1), None);
+    }
+
+    #[test]
+    fn color_matrix_mixes_channels() {
+        let stages = PipelineStages { gamma: false, white_balance: false, brightness: false, smoothing: false };
+        let mut pipeline = Pipeline::new((1.0, 1.0, 1.0), 1.0, 6600.0, 1.0, stages);
+        pipeline.set_color_matrix(IDENTITY_MATRIX);
+        assert_eq!(pipeline.apply((200, 100, 50)), (200, 100, 50));
+        // Красный немного уходит в синий, зелёный приглушается
+        pipeline.set_color_matrix([[1.0, 0.0, 0.0], [0.0, 0.5, 0.0], [0.2, 0.0, 1.0]]);
+        assert_eq!(pipeline.apply((200, 100, 50)), (200, 50, 90));
+        assert_eq!(pipeline.apply((255, 0, 255)), (255, 0, 255));
+    }
+
+    #[test]
+    fn soft_clip_keeps_hue_of_overflowing_colors() {
+        let stages = PipelineStages { gamma: false, white_balance: false, brightness: false, smoothing: false };
+        let mut pipeline = Pipeline::new((1.0, 1.0, 1.0), 1.0, 6600.0, 1.0, stages);
+        pipeline.set_color_matrix([[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        // Красный 400 обрезается до 255, а зелёный и синий остаются: оранжевый краснеет
+        assert_eq!(pipeline.apply((200, 100, 50)), (255, 100, 50));
+        pipeline.set_soft_clip(true);
+        assert_eq!(pipeline.apply((200, 100, 50)), (255, 63, 31));
+        // Цвета без переполнения не меняются
+        assert_eq!(pipeline.apply((100, 100, 50)), (200, 100, 50));
+    }
+
+    #[test]
+    fn output_filter_keeps_luminance() {
+        let mut colors = [(255, 255, 255), (0, 255, 0), (0, 0, 0)];
+        apply_output_filter(&mut colors, OutputFilter::RedOnly);
+        assert_eq!(colors, [(255, 0, 0), (182, 0, 0), (0, 0, 0)]);
+
+        let mut colors = [(0, 0, 255)];
+        apply_output_filter(&mut colors, OutputFilter::Grayscale);
+        assert_eq!(colors, [(18, 18, 18)]);
+    }
+
+    #[test]
+    fn regions_must_fit_padded_rows() {
+        // Кадр 10x4 со строками по 48 байт; у последней строки выравнивания нет
+        let (stride, frame_len) = (48, 48 * 3 + 40);
+        let region = |x2, y2| LedRegion { x1: 0, y1: 0, x2, y2, edge: None };
+        assert!(regions_fit(frame_len, stride, &[region(10, 4)]));
+        assert!(!regions_fit(frame_len, stride, &[region(12, 4)]));
+        assert!(!regions_fit(frame_len, stride, &[region(10, 5)]));
+        assert!(regions_fit(0, stride, &[LedRegion { x1: 3, y1: 2, x2: 3, y2: 9, edge: None }]));
+    }
+}
@@ -0,0 +1,984 @@
+use crate::color::{ColorMatrix, ColorMode, IDENTITY_MATRIX, OutputFilter};
+use crate::contrast::ContrastSettings;
+use crate::displays::DisplayConfig;
+use crate::format::PixelFormat;
+use crate::hotkey;
+use crate::idle::IdleAnimation;
+use crate::mirror::MirrorMode;
+use crate::nanoleaf::NanoleafConfig;
+use crate::output::{OutputConfig, OutputMode};
+use crate::packet::{ADALIGHT_CHECKSUM_XOR, ADALIGHT_MAGIC, ChannelLayout, Framing, WhiteExtraction};
+use crate::profile::Profiles;
+use crate::regions::{BottomGroupSizing, CaptureArea, Layout, MatrixWiring, Side, Thickness};
+use crate::schedule::{self, BrightnessPoint};
+use crate::segments::Segment;
+use crate::smoothing::AdaptiveSmoothing;
+use crate::strips::StripConfig;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Настройки по умолчанию, вшитые в программу: пример `config.toml` из репозитория.
+pub const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+
+/// Пауза между попытками перечитать испорченный файл настроек.
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmbilightConfig {
+    /// Кадров в секунду, можно дробное; 0 — без ограничения, с какой скоростью отдаёт захват
+    #[serde(alias = "capture_fps")]
+    pub fps: f64,
+    /// Частота вывода на ленту, если она выше частоты захвата: поток вывода достраивает
+    /// промежуточные кадры между двумя последними. Не задано или равно `fps` — как у захвата
+    #[serde(default)]
+    pub output_fps: Option<f64>,
+    pub port_name: String,
+    pub baud_rate: u32,
+    /// Таймаут записи в последовательный порт, мс
+    #[serde(default = "default_serial_timeout_ms")]
+    pub serial_timeout_ms: u64,
+    /// Вызывать `flush` порта после каждого кадра
+    #[serde(default)]
+    pub flush_after_write: bool,
+    /// Сверять число светодиодов с приветствием прошивки после открытия порта
+    #[serde(default)]
+    pub verify_led_count: bool,
+
+    pub top_led_count: usize,
+    pub left_led_count: usize,
+    pub right_led_count: usize,
+    pub bottom_left_led_count: usize,
+    pub bottom_right_led_count: usize,
+    pub offset: usize,
+    /// Светодиоды в углах, между участками сторон
+    #[serde(default)]
+    pub top_left_corner_led_count: usize,
+    #[serde(default)]
+    pub top_right_corner_led_count: usize,
+    #[serde(default)]
+    pub bottom_left_corner_led_count: usize,
+    #[serde(default)]
+    pub bottom_right_corner_led_count: usize,
+    /// Какую часть разрыва (в процентах, до 50) захватывает каждый соседний с ним светодиод
+    #[serde(default)]
+    pub bottom_gap_feather: usize,
+    /// Ширина нижних групп: пропорционально числу светодиодов или с шагом верхней стороны
+    #[serde(default)]
+    pub bottom_group_sizing: BottomGroupSizing,
+
+    pub invert_direction: bool,
+    /// Физическая позиция на ленте для каждого светодиода по порядку регионов;
+    /// пусто — лента идёт в порядке регионов
+    #[serde(default)]
+    pub led_remap: Vec<usize>,
+    /// Толщина регионов в процентах высоты экрана для сторон без своей толщины
+    pub pixel_thickness: usize,
+    /// Толщина регионов стороны: `"5%"` размера экрана поперёк стороны или `"40px"`
+    #[serde(default)]
+    pub thickness_top: Option<Thickness>,
+    #[serde(default)]
+    pub thickness_bottom: Option<Thickness>,
+    #[serde(default)]
+    pub thickness_left: Option<Thickness>,
+    #[serde(default)]
+    pub thickness_right: Option<Thickness>,
+    /// Минимальное число усредняемых пикселей (с учётом `sample_step`) в регионе; тонкие
+    /// регионы расширяются вглубь экрана. Также принимается под именем `min_pixels_per_led`
+    #[serde(default, alias = "min_pixels_per_led")]
+    pub min_pixels_per_region: usize,
+
+    /// Формат пикселей захвата; всё, кроме BGRA, перед усреднением переводится в BGRA
+    #[serde(default)]
+    pub pixel_format: PixelFormat,
+
+    /// Шаг выборки пикселей региона по x и y: 1 — все пиксели, 4 — каждый четвёртый
+    #[serde(default = "default_sample_step")]
+    pub sample_step: usize,
+    /// Процент самых тёмных и самых ярких пикселей региона, отбрасываемых
+    /// перед усреднением (0 — обычное среднее)
+    #[serde(default)]
+    pub region_trim_percent: usize,
+    /// Цвет региона: среднее или самый частый цвет
+    #[serde(default)]
+    pub color_mode: ColorMode,
+    /// Насколько быстро падает вес пикселя от края экрана к центру региона
+    /// (0 — все пиксели региона равноценны)
+    #[serde(default)]
+    pub edge_weight_falloff: f32,
+    /// Усреднять пиксели региона в линейном свете (точнее, но медленнее)
+    #[serde(default)]
+    pub linear_blend: bool,
+    /// Считать среднее регионов по таблице сумм кадра (быстрее для толстых регионов);
+    /// действует только без прореживания, усечения, весов и линейного света
+    #[serde(default)]
+    pub integral_image: bool,
+
+    /// Отступ регионов от края экрана внутрь для каждой стороны, % (компенсация overscan)
+    #[serde(default)]
+    pub inset_top: usize,
+    #[serde(default)]
+    pub inset_bottom: usize,
+    #[serde(default)]
+    pub inset_left: usize,
+    #[serde(default)]
+    pub inset_right: usize,
+
+    /// Множитель яркости светодиодов каждой стороны (1.0 — без изменений),
+    /// например, чтобы приглушить низ, отражающийся от стола
+    #[serde(default = "default_side_brightness")]
+    pub brightness_top: f32,
+    #[serde(default = "default_side_brightness")]
+    pub brightness_bottom: f32,
+    #[serde(default = "default_side_brightness")]
+    pub brightness_left: f32,
+    #[serde(default = "default_side_brightness")]
+    pub brightness_right: f32,
+    /// Одна половина ленты повторяет отражение другой (для симметричных лент)
+    #[serde(default)]
+    pub mirror_mode: MirrorMode,
+
+    /// Раскладка светодиодов: лента по краям или матрица
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub matrix_columns: usize,
+    #[serde(default)]
+    pub matrix_rows: usize,
+    #[serde(default)]
+    pub matrix_wiring: MatrixWiring,
+    /// JSON-конфигурация Hyperion с раскладкой для `layout = "hyperion"`
+    pub hyperion_layout_file: Option<String>,
+
+    pub brightness: usize,
+    /// Нижний порог яркости светодиода в процентах (0 — без порога)
+    #[serde(default)]
+    pub min_brightness: usize,
+    /// Фильтр итоговых цветов: только красный или оттенки серого
+    #[serde(default)]
+    pub output_filter: OutputFilter,
+    pub white_balance_temperature: f32,
+    /// Матрица коррекции цвета 3x3 по строкам, применяется после баланса белого
+    #[serde(default = "default_color_matrix")]
+    pub color_matrix: ColorMatrix,
+    /// Если после коррекции какой-то канал больше 255, уменьшать все каналы
+    /// пропорционально, сохраняя оттенок, вместо обрезки каждого канала
+    #[serde(default)]
+    pub soft_clip: bool,
+    /// Баланс белого до гаммы, а не после (по умолчанию: гамма, баланс белого, яркость)
+    #[serde(default)]
+    pub white_balance_before_gamma: bool,
+    pub gamma: f32,
+    /// Гамма отдельных каналов; не заданная берётся из `gamma`
+    pub gamma_r: Option<f32>,
+    pub gamma_g: Option<f32>,
+    pub gamma_b: Option<f32>,
+    /// Множитель насыщенности среднего цвета (в HSV), 1.0 — без изменений
+    #[serde(default = "default_saturation")]
+    pub saturation: f32,
+    /// Доля предыдущего цвета при сглаживании между кадрами (0.0–1.0), 0 — выключено
+    #[serde(default)]
+    pub smoothing: f32,
+    /// Сглаживать в линейном свете, а не в sRGB: переходы выглядят равномернее
+    #[serde(default)]
+    pub linear_smoothing: bool,
+    /// Сглаживание по движению: если заданы оба, `smoothing` не используется, а сила
+    /// сглаживания каждого светодиода меняется от `smoothing_max` (цвет почти не меняется)
+    /// до `smoothing_min` (изменение канала на `smoothing_motion_threshold` и больше)
+    pub smoothing_min: Option<f32>,
+    pub smoothing_max: Option<f32>,
+    #[serde(default = "default_smoothing_motion_threshold")]
+    pub smoothing_motion_threshold: f32,
+    /// Сглаживание как постоянная времени, мс: сила за кадр считается по его реальной
+    /// длительности, поэтому не зависит от FPS. Если задано, заменяет `smoothing`
+    pub smoothing_time_ms: Option<u64>,
+    /// Этапы обработки цвета; выключаются для поиска проблем с цветами
+    #[serde(default = "default_true")]
+    pub gamma_enabled: bool,
+    #[serde(default = "default_true")]
+    pub white_balance_enabled: bool,
+    #[serde(default = "default_true")]
+    pub brightness_enabled: bool,
+    #[serde(default = "default_true")]
+    pub smoothing_enabled: bool,
+    /// Автоуровни: тёмные малоконтрастные сцены растягиваются до `auto_contrast_ceiling`
+    #[serde(default)]
+    pub auto_contrast: bool,
+    #[serde(default = "default_auto_contrast_ceiling")]
+    pub auto_contrast_ceiling: u8,
+    /// Сила растяжения 0.0–1.0
+    #[serde(default = "default_auto_contrast_strength")]
+    pub auto_contrast_strength: f32,
+    #[serde(default = "default_auto_contrast_max_gain")]
+    pub auto_contrast_max_gain: f32,
+    /// Доля прежних уровней при обновлении 0.0–1.0, чтобы яркость не «дышала»
+    #[serde(default = "default_auto_contrast_smoothing")]
+    pub auto_contrast_smoothing: f32,
+    /// Временной дизеринг дробной части цвета: меньше ступенек на малой яркости
+    #[serde(default)]
+    pub dithering: bool,
+    /// Автоматически выбирать профиль «игра» или «фильм» по изображению
+    #[serde(default)]
+    pub auto_profile: bool,
+    #[serde(default = "default_profile_motion_threshold")]
+    pub profile_motion_threshold: f32,
+    #[serde(default = "default_profile_saturation_threshold")]
+    pub profile_saturation_threshold: f32,
+    #[serde(default = "default_profile_hysteresis")]
+    pub profile_hysteresis: f32,
+    #[serde(default = "default_profile_hold_secs")]
+    pub profile_hold_secs: u64,
+    /// Переопределения настроек для профилей (`[profiles.game]`, `[profiles.movie]`)
+    #[serde(default)]
+    pub profiles: Profiles,
+
+    /// Дисплеи, вокруг которых идёт одна лента, в порядке ленты (`[[displays]]`).
+    /// Пусто — только основной дисплей с числом светодиодов из общих настроек
+    #[serde(default)]
+    pub displays: Vec<DisplayConfig>,
+
+    /// Участки ленты со своей цветокоррекцией поверх общей (`[[segments]]`)
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    /// Отдельные контроллеры на своих портах, каждый со своей частью ленты (`[[strips]]`).
+    /// Пусто — вся лента на `port_name`
+    #[serde(default)]
+    pub strips: Vec<StripConfig>,
+    /// Яркость по времени суток поверх `brightness` (`[[brightness_schedule]]`); пусто — выключено
+    #[serde(default)]
+    pub brightness_schedule: Vec<BrightnessPoint>,
+    /// Сила сглаживания границ между светодиодами при движении картинки (0.0–1.0), 0 — выключено
+    #[serde(default)]
+    pub temporal_antialiasing: f32,
+
+    /// Не пересчитывать цвета, если пиксели под регионами не изменились с прошлого кадра
+    #[serde(default)]
+    pub skip_identical_frames: bool,
+
+    /// Отправлять только изменившиеся светодиоды (нужна поддержка в прошивке, см. `packet::DeltaEncoder`)
+    #[serde(default)]
+    pub delta_packets: bool,
+    /// Максимум изменившихся светодиодов для дельта-пакета; 0 — пока дельта короче полного пакета
+    #[serde(default)]
+    pub delta_threshold: usize,
+    /// Не писать в порт кадр, совпадающий с прошлым отправленным
+    /// (с допуском `unchanged_write_tolerance` по каналу)
+    #[serde(default)]
+    pub skip_unchanged_writes: bool,
+    #[serde(default)]
+    pub unchanged_write_tolerance: u8,
+    /// RGBW-лента: в пакет добавляется четвёртый байт белого канала
+    #[serde(default)]
+    pub white_channel: bool,
+    #[serde(default)]
+    pub white_extraction: WhiteExtraction,
+    /// Порядок байт светодиода в пакете, например "GRB" или "GRBW" (по умолчанию RGB / RGBW).
+    /// Также принимается под именем `led_color_order`
+    #[serde(default, alias = "led_color_order")]
+    pub channel_order: Option<String>,
+    /// Заголовок пакета Adalight (ровно 3 символа) для прошивок с другим словом
+    #[serde(default = "default_magic_word")]
+    pub magic_word: String,
+    /// Начальное значение контрольного байта заголовка: `hi ^ lo ^ checksum_xor`
+    #[serde(default = "default_checksum_xor")]
+    pub checksum_xor: u8,
+    /// `raw` — без заголовка Adalight, только байты каналов
+    #[serde(default)]
+    pub framing: Framing,
+
+    /// Ограничение скорости вывода в байтах в секунду; кадр, которому не хватило,
+    /// уходит, когда накопится скорость, если его раньше не заменит более свежий
+    pub max_bytes_per_second: Option<u32>,
+
+    /// Порт TCP-сервера управления; если не задан, сервер не запускается
+    pub control_port: Option<u16>,
+    /// Глобальная горячая клавиша паузы, например "Ctrl+Alt+L" (только Windows)
+    #[serde(default)]
+    pub pause_hotkey: Option<String>,
+    #[serde(default = "default_static_color")]
+    pub static_color: (u8, u8, u8),
+
+    /// Индекс светодиода, который показывает состояние программы вместо цвета экрана
+    pub status_led_index: Option<usize>,
+    #[serde(default = "default_status_led_idle_color")]
+    pub status_led_idle_color: (u8, u8, u8),
+    #[serde(default = "default_status_led_active_color")]
+    pub status_led_active_color: (u8, u8, u8),
+    #[serde(default = "default_status_led_error_color")]
+    pub status_led_error_color: (u8, u8, u8),
+
+    /// Искать чёрные полосы (letterbox) сверху и снизу и не брать цвета с них
+    #[serde(default)]
+    pub detect_letterbox: bool,
+    /// Раз во сколько кадров пересчитывать высоту полос
+    #[serde(default = "default_letterbox_interval_frames")]
+    pub letterbox_interval_frames: u32,
+
+    /// Прямоугольник экрана, по которому строятся регионы (например, окно игры
+    /// в центре ультраширокого монитора); не заданные поля — весь экран
+    pub capture_x: Option<usize>,
+    pub capture_y: Option<usize>,
+    pub capture_width: Option<usize>,
+    pub capture_height: Option<usize>,
+
+    /// Захватывать только область активного окна (только Windows)
+    #[serde(default)]
+    pub follow_window_focus: bool,
+    #[serde(default = "default_focus_poll_interval_ms")]
+    pub focus_poll_interval_ms: u64,
+
+    /// Пауза после пересоздания захвата (смена разрешения/дисплея), мс
+    #[serde(default = "default_reconfigure_settle_ms")]
+    pub reconfigure_settle_ms: u64,
+    /// Сколько первых кадров после пересоздания захвата отбрасывать
+    #[serde(default = "default_reconfigure_discard_frames")]
+    pub reconfigure_discard_frames: u32,
+
+    /// Мёртвая зона канала: изменения не больше этого значения не выводятся (0 — выключено).
+    /// Не сочетается с `dithering`
+    #[serde(default)]
+    pub deadband: u8,
+
+    /// Порт HTTP-сервера с MJPEG-потоком цветов ленты (не задан — выключен)
+    #[serde(default)]
+    pub stream_port: Option<u16>,
+    /// Порт HTTP-сервера состояния (`GET /status`, `POST /config`); None — выключен
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    /// Адрес HTTP-сервера состояния и MJPEG-потока; по умолчанию только этот компьютер,
+    /// "0.0.0.0" — вся сеть
+    #[serde(default = "default_http_bind")]
+    pub http_bind: String,
+    /// Частота кадров MJPEG-потока
+    #[serde(default = "default_stream_fps")]
+    pub stream_fps: u32,
+
+    /// Куда отправлять цвета: последовательный порт или WLED по UDP (`[output]`)
+    #[serde(default)]
+    pub output: OutputConfig,
+
+    /// Вывод на панели Nanoleaf (`[nanoleaf]`)
+    #[serde(default)]
+    pub nanoleaf: Option<NanoleafConfig>,
+
+    /// Через сколько секунд неподвижной картинки лента плавно гаснет (не задано — никогда)
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Изменение канала, которое ещё считается неподвижной картинкой
+    #[serde(default = "default_idle_threshold")]
+    pub idle_threshold: u8,
+    /// Длительность затухания и возврата яркости, секунды
+    #[serde(default = "default_idle_fade_out_secs")]
+    pub idle_fade_out_secs: f32,
+    #[serde(default)]
+    pub idle_fade_in_secs: f32,
+    /// Что показывать после затухания вместо тёмной ленты
+    #[serde(default)]
+    pub idle_animation: IdleAnimation,
+
+    /// Предел тока ленты, мА: при превышении все цвета равномерно приглушаются
+    #[serde(default)]
+    pub max_power_ma: Option<u32>,
+    /// Ток одного канала светодиода на полной яркости, мА
+    #[serde(default = "default_led_channel_ma")]
+    pub led_channel_ma: f32,
+    /// Ток погашенного светодиода, мА
+    #[serde(default = "default_led_idle_ma")]
+    pub led_idle_ma: f32,
+
+    /// Плавное нарастание яркости после запуска, мс, чтобы ограничить пусковой ток (0 — выключено)
+    #[serde(default)]
+    pub soft_start_ms: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_profile_motion_threshold() -> f32 {
+    10.0
+}
+
+fn default_profile_saturation_threshold() -> f32 {
+    0.6
+}
+
+fn default_profile_hysteresis() -> f32 {
+    0.2
+}
+
+fn default_profile_hold_secs() -> u64 {
+    5
+}
+
+fn default_static_color() -> (u8, u8, u8) {
+    (255, 255, 255)
+}
+
+fn default_status_led_idle_color() -> (u8, u8, u8) {
+    (0, 0, 255)
+}
+
+fn default_status_led_active_color() -> (u8, u8, u8) {
+    (0, 255, 0)
+}
+
+fn default_status_led_error_color() -> (u8, u8, u8) {
+    (255, 0, 0)
+}
+
+fn default_focus_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_idle_threshold() -> u8 {
+    2
+}
+
+fn default_idle_fade_out_secs() -> f32 {
+    3.0
+}
+
+fn default_led_channel_ma() -> f32 {
+    20.0
+}
+
+fn default_led_idle_ma() -> f32 {
+    1.0
+}
+
+fn default_sample_step() -> usize {
+    1
+}
+
+fn default_smoothing_motion_threshold() -> f32 {
+    32.0
+}
+
+fn default_auto_contrast_ceiling() -> u8 {
+    255
+}
+
+fn default_auto_contrast_strength() -> f32 {
+    1.0
+}
+
+fn default_auto_contrast_max_gain() -> f32 {
+    3.0
+}
+
+fn default_auto_contrast_smoothing() -> f32 {
+    0.95
+}
+
+fn default_side_brightness() -> f32 {
+    1.0
+}
+
+fn default_magic_word() -> String {
+    ADALIGHT_MAGIC.to_string()
+}
+
+fn default_checksum_xor() -> u8 {
+    ADALIGHT_CHECKSUM_XOR
+}
+
+fn default_color_matrix() -> ColorMatrix {
+    IDENTITY_MATRIX
+}
+
+fn default_saturation() -> f32 {
+    1.0
+}
+
+fn default_letterbox_interval_frames() -> u32 {
+    30
+}
+
+fn default_serial_timeout_ms() -> u64 {
+    10
+}
+
+fn default_stream_fps() -> u32 {
+    10
+}
+
+fn default_http_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_reconfigure_settle_ms() -> u64 {
+    500
+}
+
+fn default_reconfigure_discard_frames() -> u32 {
+    3
+}
+
+/// Формат файла настроек; выбирается по расширению пути.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// `.json` — JSON, всё остальное — TOML.
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// Разбирает настройки в заданном формате (без проверки значений).
+pub fn parse_config(data: &str, format: ConfigFormat) -> Result<AmbilightConfig, String> {
+    match format {
+        ConfigFormat::Toml => toml::from_str(data).map_err(|e| format!("ошибка разбора TOML: {}", e)),
+        ConfigFormat::Json => serde_json::from_str(data).map_err(|e| format!("ошибка разбора JSON: {}", e)),
+    }
+}
+
+/// Сохраняет настройки в формате по расширению `path`: TOML — с комментарием
+/// о времени сохранения, JSON — без него, т. к. комментариев в JSON нет.
+/// Поля записываются в порядке объявления в `AmbilightConfig`.
+pub fn save_config(config: &AmbilightConfig, path: &str) -> Result<(), Box<dyn Error>> {
+    if ConfigFormat::from_path(path) == ConfigFormat::Json {
+        std::fs::write(path, serde_json::to_string_pretty(config)? + "\n")?;
+        return Ok(());
+    }
+    let body = toml::to_string(config)?;
+    let header = format!(
+        "# Настройки сохранены {}\n\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    std::fs::write(path, header + &body)?;
+    Ok(())
+}
+
+/// Что делать, если файл настроек не читается или не разбирается.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigErrorPolicy {
+    /// Завершиться с ошибкой (для интерактивного запуска)
+    Fail,
+    /// Громко предупредить и работать со встроенными настройками
+    Defaults,
+    /// Перечитывать файл, пока он не станет корректным
+    Retry,
+}
+
+/// Конечное неотрицательное число. TOML и JSON допускают `nan` и `inf`, а `value < 0.0`
+/// для NaN ложно, поэтому такая проверка его пропустила бы.
+fn non_negative(value: f32) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+impl AmbilightConfig {
+    /// Проверяет значения, которые TOML пропускает, но программа принять не может.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1000.0).contains(&self.fps) {
+            return Err(format!("fps должен быть в диапазоне 0–1000 (0 — без ограничения), задано {}", self.fps));
+        }
+        if let Some(output_fps) = self.output_fps
+            && !(output_fps > 0.0 && output_fps <= 1000.0)
+        {
+            return Err(format!("output_fps должен быть в диапазоне (0, 1000], задано {}", output_fps));
+        }
+        let percentages = [
+            ("offset", self.offset),
+            ("pixel_thickness", self.pixel_thickness),
+            ("brightness", self.brightness),
+            ("min_brightness", self.min_brightness),
+            ("inset_top", self.inset_top),
+            ("inset_bottom", self.inset_bottom),
+            ("inset_left", self.inset_left),
+            ("inset_right", self.inset_right),
+        ];
+        for (name, value) in percentages {
+            if value > 100 {
+                return Err(format!("{} задаётся в процентах 0–100, задано {}", name, value));
+            }
+        }
+        if self.bottom_gap_feather > 50 {
+            return Err(format!("bottom_gap_feather должен быть 0–50, задано {}", self.bottom_gap_feather));
+        }
+        if self.region_trim_percent >= 50 {
+            return Err(format!("region_trim_percent должен быть меньше 50, задано {}", self.region_trim_percent));
+        }
+
+        let side_brightness = [self.brightness_top, self.brightness_bottom, self.brightness_left, self.brightness_right];
+        if let Some(multiplier) = side_brightness.into_iter().find(|&multiplier| !non_negative(multiplier)) {
+            return Err(format!("множитель яркости стороны не может быть отрицательным, задано {}", multiplier));
+        }
+
+        let gammas = [
+            Some(self.gamma),
+            self.gamma_r,
+            self.gamma_g,
+            self.gamma_b,
+            self.profiles.game.gamma,
+            self.profiles.movie.gamma,
+        ];
+        if let Some(gamma) = gammas.into_iter().flatten().find(|&gamma| !(gamma.is_finite() && gamma > 0.0)) {
+            return Err(format!("gamma должна быть больше 0, задано {}", gamma));
+        }
+        if !(1000.0..=40000.0).contains(&self.white_balance_temperature) {
+            return Err(format!(
+                "white_balance_temperature должна быть в диапазоне 1000–40000 K, задано {}",
+                self.white_balance_temperature
+            ));
+        }
+        let smoothing = [self.smoothing]
+            .into_iter()
+            .chain(self.smoothing_min)
+            .chain(self.smoothing_max)
+            .chain(self.profiles.game.smoothing)
+            .chain(self.profiles.movie.smoothing);
+        for value in smoothing {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("smoothing должен быть в диапазоне 0.0–1.0, задано {}", value));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.smoothing_min, self.smoothing_max)
+            && min > max
+        {
+            return Err(format!("smoothing_min ({}) не может быть больше smoothing_max ({})", min, max));
+        }
+        if !(self.smoothing_motion_threshold.is_finite() && self.smoothing_motion_threshold > 0.0) {
+            return Err("smoothing_motion_threshold должен быть больше 0".to_string());
+        }
+        for (name, value) in [
+            ("auto_contrast_strength", self.auto_contrast_strength),
+            ("auto_contrast_smoothing", self.auto_contrast_smoothing),
+            ("temporal_antialiasing", self.temporal_antialiasing),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} должен быть в диапазоне 0.0–1.0, задано {}", name, value));
+            }
+        }
+        if !(self.auto_contrast_max_gain.is_finite() && self.auto_contrast_max_gain >= 1.0) {
+            return Err(format!("auto_contrast_max_gain должен быть не меньше 1.0, задано {}", self.auto_contrast_max_gain));
+        }
+        if !non_negative(self.saturation) {
+            return Err(format!("saturation не может быть отрицательной, задано {}", self.saturation));
+        }
+        if !non_negative(self.idle_fade_out_secs) || !non_negative(self.idle_fade_in_secs) {
+            return Err("idle_fade_out_secs и idle_fade_in_secs не могут быть отрицательными".to_string());
+        }
+        if !non_negative(self.edge_weight_falloff) {
+            return Err(format!("edge_weight_falloff не может быть отрицательным, задано {}", self.edge_weight_falloff));
+        }
+        if self.sample_step == 0 {
+            return Err("sample_step должен быть не меньше 1".to_string());
+        }
+        for (name, value) in [
+            ("profile_motion_threshold", self.profile_motion_threshold),
+            ("profile_saturation_threshold", self.profile_saturation_threshold),
+            ("profile_hysteresis", self.profile_hysteresis),
+            ("led_channel_ma", self.led_channel_ma),
+            ("led_idle_ma", self.led_idle_ma),
+        ] {
+            if !non_negative(value) {
+                return Err(format!("{} должен быть неотрицательным числом, задано {}", name, value));
+            }
+        }
+        // Мёртвая зона стоит после дизеринга и задержала бы его чередование ±1
+        if self.dithering && self.deadband > 0 {
+            return Err("deadband не сочетается с dithering: задайте deadband = 0".to_string());
+        }
+        if !self.displays.is_empty() && self.layout != Layout::Edges {
+            return Err("[[displays]] поддерживаются только с layout = \"edges\"".to_string());
+        }
+        // Число светодиодов зависит от раскладки и дисплеев, поэтому здесь проверяется только
+        // первый универсум, а последний — при запуске вместе с [[strips]]
+        self.output.validate_universes(0)?;
+        if !self.strips.is_empty() && self.output.mode != OutputMode::Serial {
+            return Err("[[strips]] поддерживаются только с mode = \"serial\"".to_string());
+        }
+        if !self.strips.is_empty() && !self.led_remap.is_empty() {
+            return Err("led_remap не поддерживается вместе с [[strips]]".to_string());
+        }
+        let layout = self.channel_layout()?;
+        if self.delta_packets && layout != ChannelLayout::default() {
+            return Err("delta_packets не поддерживает white_channel, channel_order, magic_word, checksum_xor и framing = \"raw\"".to_string());
+        }
+        if self.capture_width == Some(0) || self.capture_height == Some(0) {
+            return Err("capture_width и capture_height должны быть больше 0".to_string());
+        }
+        if self.max_bytes_per_second == Some(0) {
+            return Err("max_bytes_per_second должен быть больше 0".to_string());
+        }
+        if self.serial_timeout_ms == 0 {
+            return Err("serial_timeout_ms должен быть больше 0".to_string());
+        }
+        if self.stream_fps == 0 {
+            return Err("stream_fps должен быть больше 0".to_string());
+        }
+        schedule::validate_schedule(&self.brightness_schedule)?;
+        if let Some(hotkey) = &self.pause_hotkey {
+            hotkey::parse_hotkey(hotkey)?;
+        }
+        Ok(())
+    }
+
+    /// Заголовок, порядок байт и белый канал светодиода в пакете Adalight.
+    pub fn channel_layout(&self) -> Result<ChannelLayout, String> {
+        let white = self.white_channel.then_some(self.white_extraction);
+        let default_order = if self.white_channel { "RGBW" } else { "RGB" };
+        ChannelLayout::parse(self.channel_order.as_deref().unwrap_or(default_order), white)?
+            .with_header(&self.magic_word, self.checksum_xor)
+            .map(|layout| layout.with_framing(self.framing))
+    }
+
+    /// Область захвата на экране `width` x `height` по `capture_*`, обрезанная краями экрана.
+    /// Если начало области за пределами экрана, захватывается весь экран, иначе регионы
+    /// оказались бы пустыми и лента молча погасла.
+    pub fn capture_area(&self, width: usize, height: usize) -> CaptureArea {
+        let x = self.capture_x.unwrap_or(0);
+        let y = self.capture_y.unwrap_or(0);
+        if x >= width || y >= height {
+            warn!(
+                "capture_x = {}, capture_y = {} за пределами экрана {}x{}: захватывается весь экран",
+                x, y, width, height
+            );
+            return CaptureArea { x: 0, y: 0, width, height };
+        }
+        CaptureArea {
+            x,
+            y,
+            width: self.capture_width.unwrap_or(width).min(width - x),
+            height: self.capture_height.unwrap_or(height).min(height - y),
+        }
+    }
+
+    /// Длительность кадра с точностью до наносекунд; нулевая при `fps = 0`.
+    pub fn frame_duration(&self) -> Duration {
+        if self.fps == 0.0 { Duration::ZERO } else { Duration::from_secs_f64(1.0 / self.fps) }
+    }
+
+    /// Период вывода с промежуточными кадрами; `None` — кадры выводятся по мере захвата.
+    pub fn output_interval(&self) -> Option<Duration> {
+        self.output_fps
+            .filter(|&output_fps| output_fps > 0.0 && output_fps != self.fps)
+            .map(|output_fps| Duration::from_secs_f64(1.0 / output_fps))
+    }
+
+    /// Параметры автоуровней, если они включены.
+    pub fn contrast_settings(&self) -> Option<ContrastSettings> {
+        self.auto_contrast.then_some(ContrastSettings {
+            ceiling: self.auto_contrast_ceiling,
+            strength: self.auto_contrast_strength,
+            max_gain: self.auto_contrast_max_gain,
+            smoothing: self.auto_contrast_smoothing,
+        })
+    }
+
+    /// Сглаживание по движению, если заданы `smoothing_min` и `smoothing_max`.
+    pub fn adaptive_smoothing(&self) -> Option<AdaptiveSmoothing> {
+        Some(AdaptiveSmoothing {
+            min: self.smoothing_min?,
+            max: self.smoothing_max?,
+            motion_threshold: self.smoothing_motion_threshold,
+        })
+    }
+
+    /// Толщина регионов стороны в пикселях экрана `width` x `height`.
+    pub fn side_thickness(&self, side: Side, width: usize, height: usize) -> usize {
+        let (thickness, across) = match side {
+            Side::Top => (self.thickness_top, height),
+            Side::Bottom => (self.thickness_bottom, height),
+            Side::Left => (self.thickness_left, width),
+            Side::Right => (self.thickness_right, width),
+        };
+        thickness.map_or(height * self.pixel_thickness / 100, |thickness| thickness.pixels(across))
+    }
+
+    /// Множитель яркости светодиодов стороны `side`.
+    pub fn side_brightness(&self, side: Side) -> f32 {
+        match side {
+            Side::Top => self.brightness_top,
+            Side::Bottom => self.brightness_bottom,
+            Side::Left => self.brightness_left,
+            Side::Right => self.brightness_right,
+        }
+    }
+
+    /// Гамма каналов R, G, B: собственная гамма канала или общая `gamma`.
+    pub fn channel_gamma(&self, gamma: f32) -> (f32, f32, f32) {
+        (
+            self.gamma_r.unwrap_or(gamma),
+            self.gamma_g.unwrap_or(gamma),
+            self.gamma_b.unwrap_or(gamma),
+        )
+    }
+}
+
+fn read_config(path: &str) -> Result<AmbilightConfig, Box<dyn Error>> {
+    let config_data = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("файл настроек {} не найден", path),
+        _ => format!("не удалось прочитать {}: {}", path, e),
+    })?;
+    let config = parse_config(&config_data, ConfigFormat::from_path(path))?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Время последнего изменения файла настроек (`None`, если его нет).
+pub fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Загружает настройки из `path`, при ошибке поступая согласно `policy`.
+pub fn load_config(path: &str, policy: ConfigErrorPolicy) -> Result<AmbilightConfig, Box<dyn Error>> {
+    loop {
+        let error = match read_config(path) {
+            Ok(config) => return Ok(config),
+            Err(e) => e,
+        };
+        match policy {
+            ConfigErrorPolicy::Fail => return Err(error),
+            ConfigErrorPolicy::Defaults => {
+                warn!("Не удалось загрузить {}: {}", path, error);
+                warn!("Используются встроенные настройки по умолчанию");
+                return Ok(toml::from_str(DEFAULT_CONFIG)?);
+            }
+            ConfigErrorPolicy::Retry => {
+                warn!("Не удалось загрузить {}: {}. Повтор через {:?}", path, error, RETRY_INTERVAL);
+                thread::sleep(RETRY_INTERVAL);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_defaults_parse() {
+        let config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert!(config.fps > 0.0);
+    }
+
+    #[test]
+    fn bundled_defaults_are_valid() {
+        let config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn fps_may_be_fractional_or_uncapped() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.frame_duration(), Duration::from_secs_f64(1.0 / 60.0));
+        config.fps = 75.0;
+        assert_eq!(config.frame_duration().as_nanos(), 13_333_333);
+        config.fps = 0.0;
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.frame_duration(), Duration::ZERO);
+
+        config.fps = -1.0;
+        assert!(config.validate().unwrap_err().contains("fps"));
+        config.fps = f64::NAN;
+        assert!(config.validate().unwrap_err().contains("fps"));
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.offset = 150;
+        assert!(config.validate().unwrap_err().contains("offset"));
+
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.smoothing = 0.5;
+        config.profiles.movie.smoothing = Some(-0.1);
+        assert!(config.validate().unwrap_err().contains("smoothing"));
+    }
+
+    #[test]
+    fn nan_and_infinite_values_are_rejected() {
+        // TOML разбирает nan как обычное число
+        let source = DEFAULT_CONFIG.replace("\ngamma = 1.694", "\ngamma = nan");
+        let config: AmbilightConfig = toml::from_str(&source).unwrap();
+        assert!(config.validate().unwrap_err().contains("gamma"));
+
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.saturation = f32::NAN;
+        assert!(config.validate().unwrap_err().contains("saturation"));
+
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.led_channel_ma = f32::INFINITY;
+        assert!(config.validate().unwrap_err().contains("led_channel_ma"));
+    }
+
+    #[test]
+    fn deadband_and_dithering_are_exclusive() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.deadband, 0);
+        config.dithering = true;
+        assert!(config.validate().is_ok());
+        config.deadband = 1;
+        assert!(config.validate().unwrap_err().contains("dithering"));
+    }
+
+    #[test]
+    fn capture_area_is_clipped_to_the_screen() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.capture_area(3440, 1440), CaptureArea { x: 0, y: 0, width: 3440, height: 1440 });
+
+        (config.capture_x, config.capture_width) = (Some(760), Some(1920));
+        assert_eq!(config.capture_area(3440, 1440), CaptureArea { x: 760, y: 0, width: 1920, height: 1440 });
+        // Прямоугольник, вылезающий за экран, обрезается его краем
+        assert_eq!(config.capture_area(1920, 1080), CaptureArea { x: 760, y: 0, width: 1160, height: 1080 });
+        // Начало за краем экрана — весь экран, а не пустая область
+        assert_eq!(config.capture_area(640, 480), CaptureArea { x: 0, y: 0, width: 640, height: 480 });
+        config.capture_y = Some(1440);
+        assert_eq!(config.capture_area(3440, 1440), CaptureArea { x: 0, y: 0, width: 3440, height: 1440 });
+
+        config.capture_width = Some(0);
+        assert!(config.validate().unwrap_err().contains("capture_width"));
+    }
+
+    #[test]
+    fn led_color_order_is_an_alias_of_channel_order() {
+        let config: AmbilightConfig = toml::from_str(&format!("{}\nled_color_order = \"GRB\"", DEFAULT_CONFIG)).unwrap();
+        assert_eq!(config.channel_order.as_deref(), Some("GRB"));
+        assert!(config.validate().is_ok());
+
+        let config: AmbilightConfig = toml::from_str(&format!("{}\nled_color_order = \"RGG\"", DEFAULT_CONFIG)).unwrap();
+        assert!(config.validate().unwrap_err().contains("RGG"));
+    }
+
+    #[test]
+    fn serialized_config_parses_back() {
+        let source = format!(
+            "{}\n[[segments]]\nname = \"top\"\nstart = 0\nend = 29\n\n[profiles.game]\ngamma = 2.0\n",
+            DEFAULT_CONFIG
+        );
+        let config: AmbilightConfig = toml::from_str(&source).unwrap();
+        let saved = toml::to_string(&config).unwrap();
+        let reloaded: AmbilightConfig = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.segments.len(), 1);
+        assert_eq!(reloaded.profiles.game.gamma, Some(2.0));
+        assert_eq!(reloaded.port_name, config.port_name);
+    }
+
+    #[test]
+    fn json_config_matches_toml() {
+        assert_eq!(ConfigFormat::from_path("settings/ambilight.JSON"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("config.toml"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("config"), ConfigFormat::Toml);
+
+        let from_toml = parse_config(DEFAULT_CONFIG, ConfigFormat::Toml).unwrap();
+        let json = serde_json::to_string(&from_toml).unwrap();
+        let from_json = parse_config(&json, ConfigFormat::Json).unwrap();
+        assert_eq!(format!("{:?}", from_json), format!("{:?}", from_toml));
+
+        let error = parse_config("{ \"fps\": ", ConfigFormat::Json).unwrap_err();
+        assert!(error.starts_with("ошибка разбора JSON"));
+        assert!(parse_config("fps = ", ConfigFormat::Toml).unwrap_err().starts_with("ошибка разбора TOML"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Автоуровни: растягивание диапазона яркости светодиодов в тёмных, малоконтрастных сценах.
+
+use crate::color::luma;
+
+/// Параметры автоуровней.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContrastSettings {
+    /// Яркость (0–255), до которой растягивается самый яркий светодиод кадра
+    pub ceiling: u8,
+    /// Сила растяжения: 0 — без изменений, 1 — полное
+    pub strength: f32,
+    /// Наибольшее усиление, чтобы почти чёрный кадр не превращался в шум
+    pub max_gain: f32,
+    /// Доля прежних уровней при обновлении (0.0–1.0): защита от «дыхания» яркости
+    pub smoothing: f32,
+}
+
+/// Растягивает цвета так, чтобы самый тёмный светодиод кадра уходил в чёрный,
+/// а самый яркий поднимался до `ceiling`. Уровни сглаживаются между кадрами.
+#[derive(Default)]
+pub struct AutoContrast {
+    /// Сглаженные чёрная точка и усиление; `None` до первого кадра
+    levels: Option<(f32, f32)>,
+}
+
+impl AutoContrast {
+    pub fn apply(&mut self, colors: &mut [(u8, u8, u8)], settings: &ContrastSettings) {
+        let (Some(min), Some(max)) = (
+            colors.iter().map(|&color| luma(color)).reduce(f32::min),
+            colors.iter().map(|&color| luma(color)).reduce(f32::max),
+        ) else {
+            return;
+        };
+
+        let strength = settings.strength.clamp(0.0, 1.0);
+        let black = min * strength;
+        let range = (max - black).max(1.0);
+        let full_gain = (settings.ceiling as f32 / range).clamp(1.0, settings.max_gain.max(1.0));
+        let target = (black, 1.0 + (full_gain - 1.0) * strength);
+
+        let smoothing = settings.smoothing.clamp(0.0, 1.0);
+        let (black, gain) = match self.levels {
+            Some((black, gain)) => (
+                black + (1.0 - smoothing) * (target.0 - black),
+                gain + (1.0 - smoothing) * (target.1 - gain),
+            ),
+            None => target,
+        };
+        self.levels = Some((black, gain));
+
+        let stretch = |c: u8| ((c as f32 - black) * gain).round().clamp(0.0, 255.0) as u8;
+        for (r, g, b) in colors.iter_mut() {
+            (*r, *g, *b) = (stretch(*r), stretch(*g), stretch(*b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dim_scene_is_stretched_to_the_ceiling() {
+        let settings = ContrastSettings { ceiling: 200, strength: 1.0, max_gain: 8.0, smoothing: 0.0 };
+        let mut contrast = AutoContrast::default();
+        let mut colors = [(20, 20, 20), (60, 60, 60)];
+        contrast.apply(&mut colors, &settings);
+        assert_eq!(colors, [(0, 0, 0), (200, 200, 200)]);
+
+        // Усиление ограничено: почти чёрный кадр не разгоняется до потолка
+        let mut colors = [(0, 0, 0), (10, 10, 10)];
+        contrast.apply(&mut colors, &settings);
+        assert_eq!(colors, [(0, 0, 0), (80, 80, 80)]);
+    }
+
+    #[test]
+    fn levels_change_gradually() {
+        let settings = ContrastSettings { ceiling: 255, strength: 1.0, max_gain: 4.0, smoothing: 0.5 };
+        let mut contrast = AutoContrast::default();
+        contrast.apply(&mut [(0, 0, 0), (255, 255, 255)], &settings);
+        // Сцена потемнела: усиление растёт от 1 к 2 не сразу, а наполовину
+        let mut colors = [(0, 0, 0), (128, 128, 128)];
+        contrast.apply(&mut colors, &settings);
+        assert_eq!(colors[1], (192, 192, 192));
+    }
+}
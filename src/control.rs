@@ -0,0 +1,155 @@
+use crate::color::PipelineStages;
+use crate::config::{self, AmbilightConfig};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Режим работы подсветки.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Цвета берутся с экрана
+    Screen,
+    /// Вся лента горит одним цветом
+    Static,
+    /// Лента погашена
+    Off,
+}
+
+/// Состояние, которое можно менять на лету и которое читает главный цикл.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RuntimeState {
+    pub mode: Mode,
+    /// Множитель яркости 0.0–1.0
+    pub brightness: f32,
+    pub static_color: (u8, u8, u8),
+    pub stages: PipelineStages,
+    /// Пауза: лента погашена, кадры не захватываются
+    pub paused: bool,
+}
+
+pub type SharedState = Arc<Mutex<RuntimeState>>;
+
+/// Этап обработки цвета, который можно выключить командой `set_stage`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stage {
+    Gamma,
+    WhiteBalance,
+    Brightness,
+    Smoothing,
+}
+
+/// Команда протокола управления: одна JSON-строка на команду.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", content = "value", rename_all = "snake_case")]
+enum Command {
+    SetBrightness(f32),
+    SetMode(Mode),
+    SetColor((u8, u8, u8)),
+    SetStage { stage: Stage, enabled: bool },
+    SetPaused(bool),
+    GetState,
+    /// Сохранить текущие настройки в TOML: в указанный путь или в загруженный файл
+    SaveConfig(Option<String>),
+}
+
+/// То, с чем работает сервер управления.
+#[derive(Clone)]
+pub struct ControlContext {
+    pub state: SharedState,
+    /// Текущие настройки (обновляются при перечитывании config.toml)
+    pub config: Arc<Mutex<AmbilightConfig>>,
+    pub config_path: String,
+}
+
+/// Настройки с применёнными изменениями, сделанными на лету.
+fn effective_config(config: &AmbilightConfig, state: &RuntimeState) -> AmbilightConfig {
+    let mut config = config.clone();
+    config.brightness = (state.brightness * 100.0).round() as usize;
+    config.static_color = state.static_color;
+    config.gamma_enabled = state.stages.gamma;
+    config.white_balance_enabled = state.stages.white_balance;
+    config.brightness_enabled = state.stages.brightness;
+    config.smoothing_enabled = state.stages.smoothing;
+    config
+}
+
+/// Применяет команду к состоянию и возвращает JSON-ответ.
+fn handle_command(line: &str, context: &ControlContext) -> serde_json::Value {
+    let command: Command = match serde_json::from_str(line) {
+        Ok(command) => command,
+        Err(e) => return serde_json::json!({ "ok": false, "error": e.to_string() }),
+    };
+
+    let mut state = context.state.lock().unwrap();
+    match command {
+        Command::SetBrightness(value) => {
+            if !(0.0..=1.0).contains(&value) {
+                return serde_json::json!({
+                    "ok": false,
+                    "error": "brightness должна быть в диапазоне 0.0–1.0",
+                });
+            }
+            state.brightness = value;
+        }
+        Command::SetMode(mode) => state.mode = mode,
+        Command::SetColor(color) => state.static_color = color,
+        Command::SetStage { stage, enabled } => match stage {
+            Stage::Gamma => state.stages.gamma = enabled,
+            Stage::WhiteBalance => state.stages.white_balance = enabled,
+            Stage::Brightness => state.stages.brightness = enabled,
+            Stage::Smoothing => state.stages.smoothing = enabled,
+        },
+        Command::SetPaused(paused) => state.paused = paused,
+        Command::GetState => {}
+        Command::SaveConfig(path) => {
+            let path = path.as_deref().unwrap_or(&context.config_path);
+            if let Err(e) = config::save_config(&effective_config(&context.config.lock().unwrap(), &state), path) {
+                return serde_json::json!({ "ok": false, "error": e.to_string() });
+            }
+            info!("Настройки сохранены в {}", path);
+        }
+    }
+    serde_json::json!({ "ok": true, "state": *state })
+}
+
+fn handle_client(stream: TcpStream, context: ControlContext) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_command(&line, &context);
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+/// Запускает TCP-сервер управления на localhost в фоновом потоке.
+/// Каждое подключение обслуживается в отдельном потоке.
+pub fn spawn_server(port: u16, context: ControlContext) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    info!("Сервер управления слушает порт {}", port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let context = context.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, context) {
+                            warn!("Ошибка клиента управления: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Ошибка подключения к серверу управления: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use log::warn;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+
+/// Параметры фонового режима (`--daemon`).
+#[derive(Clone)]
+pub struct DaemonOptions {
+    /// `--pid-file <файл>`
+    pub pid_file: String,
+    /// `--log-file <файл>`: сюда перенаправляется вывод программы
+    pub log_file: String,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        DaemonOptions { pid_file: "ambilight.pid".to_string(), log_file: "ambilight.log".to_string() }
+    }
+}
+
+fn open_log(path: &str) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn write_pid_file(path: &str) -> io::Result<()> {
+    fs::write(path, format!("{}\n", std::process::id()))
+}
+
+/// Удаляет PID-файл при завершении.
+pub fn remove_pid_file(options: &DaemonOptions) {
+    if let Err(e) = fs::remove_file(&options.pid_file) {
+        warn!("Не удалось удалить PID-файл {}: {}", options.pid_file, e);
+    }
+}
+
+/// Фоновый режим для автозапуска при входе в систему (`--background`): журнал
+/// уходит в файл с ротацией, а на Windows программа отпускает окно консоли.
+pub fn run_in_background(log_file: &str) -> io::Result<()> {
+    crate::logging::log_to_file(log_file)?;
+    #[cfg(windows)]
+    {
+        // SAFETY: функция без аргументов; после неё вывод в консоль просто пропадает
+        unsafe {
+            windows_sys::Win32::System::Console::FreeConsole();
+        }
+    }
+    Ok(())
+}
+
+/// Уходит в фон: отделяется от терминала, перенаправляет вывод в лог и пишет PID-файл.
+/// Рабочий каталог не меняется, чтобы относительный путь к config.toml продолжал работать.
+/// Вызывать до запуска любых потоков.
+#[cfg(unix)]
+pub fn daemonize(options: &DaemonOptions) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let log = open_log(&options.log_file)?;
+    let null = File::open("/dev/null")?;
+    // SAFETY: потоков ещё нет, после fork в дочернем процессе продолжается обычный код
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => std::process::exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+    }
+    write_pid_file(&options.pid_file)
+}
+
+#[cfg(windows)]
+mod service {
+    use crate::shutdown;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerW, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_SHUTDOWN,
+        SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP_PENDING,
+        SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+        StartServiceCtrlDispatcherW,
+    };
+
+    /// Работа службы; забирается из service_main ровно один раз.
+    pub static RUN: Mutex<Option<Box<dyn FnOnce() + Send>>> = Mutex::new(None);
+    static STATUS_HANDLE: AtomicPtr<core::ffi::c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+    fn service_name() -> Vec<u16> {
+        "Ambilight".encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn set_state(state: SERVICE_STATUS_CURRENT_STATE) {
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 },
+            dwWin32ExitCode: 0,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: if state == SERVICE_STOP_PENDING { 3000 } else { 0 },
+        };
+        // SAFETY: дескриптор получен от RegisterServiceCtrlHandlerW
+        unsafe {
+            SetServiceStatus(STATUS_HANDLE.load(Ordering::Relaxed), &status);
+        }
+    }
+
+    unsafe extern "system" fn control_handler(control: u32) {
+        if control == SERVICE_CONTROL_STOP || control == SERVICE_CONTROL_SHUTDOWN {
+            shutdown::request_stop();
+            set_state(SERVICE_STOP_PENDING);
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let name = service_name();
+        // SAFETY: имя — строка UTF-16 с завершающим нулём
+        let handle = unsafe { RegisterServiceCtrlHandlerW(name.as_ptr(), Some(control_handler)) };
+        STATUS_HANDLE.store(handle, Ordering::Relaxed);
+        set_state(SERVICE_RUNNING);
+        if let Some(run) = RUN.lock().unwrap().take() {
+            run();
+        }
+        set_state(SERVICE_STOPPED);
+    }
+
+    /// Передаёт управление диспетчеру служб; возвращается, когда служба остановлена.
+    pub fn dispatch() -> std::io::Result<()> {
+        let mut name = service_name();
+        let table = [
+            SERVICE_TABLE_ENTRYW { lpServiceName: name.as_mut_ptr(), lpServiceProc: Some(service_main) },
+            SERVICE_TABLE_ENTRYW { lpServiceName: std::ptr::null_mut(), lpServiceProc: None },
+        ];
+        // SAFETY: таблица завершается нулевой записью и живёт до возврата из функции
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Запускает `run` как службу Windows: сообщает диспетчеру служб о состоянии
+/// и по команде остановки просит главный цикл завершиться. Вывод идёт в лог.
+/// Служба запускается из System32, поэтому рабочим каталогом становится каталог программы.
+#[cfg(windows)]
+pub fn run_service(options: &DaemonOptions, run: impl FnOnce() + Send + 'static) -> io::Result<()> {
+    use std::os::windows::io::IntoRawHandle;
+    use windows_sys::Win32::System::Console::{STD_ERROR_HANDLE, STD_OUTPUT_HANDLE, SetStdHandle};
+
+    if let Some(dir) = std::env::current_exe()?.parent() {
+        std::env::set_current_dir(dir)?;
+    }
+    let log = open_log(&options.log_file)?.into_raw_handle();
+    // SAFETY: дескриптор открытого файла остаётся живым до конца программы
+    unsafe {
+        SetStdHandle(STD_OUTPUT_HANDLE, log);
+        SetStdHandle(STD_ERROR_HANDLE, log);
+    }
+    write_pid_file(&options.pid_file)?;
+
+    *service::RUN.lock().unwrap() = Some(Box::new(run));
+    service::dispatch()
+}
@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+use std::net::UdpSocket;
+
+/// Стандартный порт DDP.
+pub const DDP_PORT: u16 = 4048;
+/// Данных в одном пакете: 480 светодиодов по 3 байта, чтобы пакет помещался в MTU Ethernet.
+pub const MAX_PAYLOAD: usize = 1440;
+const HEADER_LEN: usize = 10;
+
+/// Версия протокола 1 в старших битах первого байта.
+const FLAG_VERSION_1: u8 = 0x40;
+/// Последний пакет кадра: контроллер выводит накопленные данные.
+const FLAG_PUSH: u8 = 0x01;
+/// Тип данных: RGB, по 8 бит на канал.
+const DATA_TYPE_RGB8: u8 = 0x0B;
+/// Устройство по умолчанию на контроллере.
+const DESTINATION_DEFAULT: u8 = 0x01;
+
+/// Пакет DDP с частью кадра, которая начинается с байта `offset`.
+fn build_ddp_packet(sequence: u8, offset: usize, data: &[u8], push: bool, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.push(if push { FLAG_VERSION_1 | FLAG_PUSH } else { FLAG_VERSION_1 });
+    buf.push(sequence & 0x0F);
+    buf.push(DATA_TYPE_RGB8);
+    buf.push(DESTINATION_DEFAULT);
+    buf.extend_from_slice(&(offset as u32).to_be_bytes());
+    buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+/// Вывод на контроллер по DDP (WLED, ESPixelStick, xLights): принимает подряд идущие тройки
+/// R, G, B и при каждом `write` делит их на пакеты по [`MAX_PAYLOAD`] байт; последний
+/// пакет кадра помечается флагом PUSH.
+pub struct DdpUdp {
+    socket: UdpSocket,
+    sequence: u8,
+    packet: Vec<u8>,
+}
+
+impl DdpUdp {
+    pub fn connect(address: &str, port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((address, port))?;
+        Ok(DdpUdp { socket, sequence: 0, packet: Vec::with_capacity(HEADER_LEN + MAX_PAYLOAD) })
+    }
+}
+
+impl Write for DdpUdp {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Номер последовательности 1–15, ноль означает «не используется»
+        self.sequence = self.sequence % 15 + 1;
+        let chunks = buf.len().div_ceil(MAX_PAYLOAD);
+        for (i, data) in buf.chunks(MAX_PAYLOAD).enumerate() {
+            build_ddp_packet(self.sequence, i * MAX_PAYLOAD, data, i + 1 == chunks, &mut self.packet);
+            self.socket.send(&self.packet)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ddp_header_carries_offset_length_and_push() {
+        let mut buf = Vec::new();
+        build_ddp_packet(3, MAX_PAYLOAD, &[1, 2, 3], true, &mut buf);
+        assert_eq!(buf, [0x41, 3, 0x0B, 1, 0, 0, 0x05, 0xA0, 0, 3, 1, 2, 3]);
+
+        build_ddp_packet(18, 0, &[0; MAX_PAYLOAD], false, &mut buf);
+        assert_eq!(&buf[..HEADER_LEN], [0x40, 2, 0x0B, 1, 0, 0, 0, 0, 0x05, 0xA0]);
+        assert_eq!(buf.len(), HEADER_LEN + MAX_PAYLOAD);
+    }
+}
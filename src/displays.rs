@@ -0,0 +1,160 @@
+use crate::color::{self, Pipeline, Sampling, SummedAreaTable};
+use crate::config::AmbilightConfig;
+use crate::format::{self, PixelFormat};
+use crate::regions::{LedRegion, create_led_regions};
+use log::{error, info};
+use scrap::{Capturer, Display};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Дисплей, вокруг которого идёт часть ленты (`[[displays]]`).
+/// Число светодиодов задаётся для каждого дисплея отдельно.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    /// Номер дисплея в списке `Display::all()`
+    pub index: usize,
+    #[serde(default)]
+    pub top_led_count: usize,
+    #[serde(default)]
+    pub left_led_count: usize,
+    #[serde(default)]
+    pub right_led_count: usize,
+    #[serde(default)]
+    pub bottom_left_led_count: usize,
+    #[serde(default)]
+    pub bottom_right_led_count: usize,
+}
+
+impl DisplayConfig {
+    /// Общие настройки с числом светодиодов этого дисплея.
+    pub fn apply_to(&self, config: &AmbilightConfig) -> AmbilightConfig {
+        let mut config = config.clone();
+        config.top_led_count = self.top_led_count;
+        config.left_led_count = self.left_led_count;
+        config.right_led_count = self.right_led_count;
+        config.bottom_left_led_count = self.bottom_left_led_count;
+        config.bottom_right_led_count = self.bottom_right_led_count;
+        config
+    }
+}
+
+/// Открывает захват дисплея по номеру в `Display::all()`.
+pub fn open_display(index: usize) -> io::Result<Capturer> {
+    let display = Display::all()?.into_iter().nth(index).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("дисплей {} не найден", index))
+    })?;
+    Capturer::new(display)
+}
+
+/// Дополнительный дисплей: свой захват и свои регионы, посчитанные по его разрешению.
+struct ExtraDisplay {
+    index: usize,
+    /// Общие настройки с числом светодиодов этого дисплея
+    config: AmbilightConfig,
+    capturer: Capturer,
+    regions: Vec<LedRegion>,
+    /// Последние цвета; остаются прежними, пока дисплей не отдал новый кадр
+    colors: Vec<(u8, u8, u8)>,
+    dither_residuals: Vec<[u8; 3]>,
+    summed_area_table: SummedAreaTable,
+}
+
+/// Дисплеи после первого из `[[displays]]`. Первый захватывается основным циклом,
+/// цвета остальных дописываются за ним в порядке списка.
+#[derive(Default)]
+pub struct ExtraDisplays {
+    displays: Vec<ExtraDisplay>,
+    converted_frame: Vec<u8>,
+    dithering: bool,
+    integral_image: bool,
+}
+
+impl ExtraDisplays {
+    pub fn open(config: &AmbilightConfig) -> io::Result<Self> {
+        let mut displays = Vec::new();
+        for display in config.displays.iter().skip(1) {
+            let config = display.apply_to(config);
+            let capturer = open_display(display.index)?;
+            let regions = create_led_regions(&config, capturer.width(), capturer.height());
+            info!(
+                "Дисплей {}: {}x{}, светодиодов: {}",
+                display.index,
+                capturer.width(),
+                capturer.height(),
+                regions.len()
+            );
+            displays.push(ExtraDisplay {
+                index: display.index,
+                colors: vec![(0, 0, 0); regions.len()],
+                dither_residuals: Vec::new(),
+                summed_area_table: SummedAreaTable::default(),
+                config,
+                capturer,
+                regions,
+            });
+        }
+        Ok(ExtraDisplays {
+            displays,
+            converted_frame: Vec::new(),
+            dithering: config.dithering,
+            integral_image: config.integral_image,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.displays.is_empty()
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.displays.iter().map(|display| display.regions.len()).sum()
+    }
+
+    /// Захватывает новые кадры дисплеев (без ожидания) и дописывает их цвета в `colors`.
+    pub fn append_colors(
+        &mut self,
+        colors: &mut Vec<(u8, u8, u8)>,
+        pixel_format: PixelFormat,
+        sampling: Sampling,
+        pipeline: &Pipeline,
+    ) {
+        for display in &mut self.displays {
+            let (width, height) = (display.capturer.width(), display.capturer.height());
+            match display.capturer.frame() {
+                Ok(frame) => {
+                    let (frame, stride) =
+                        match format::bgra_frame(&frame, pixel_format, width, height, &mut self.converted_frame) {
+                            Ok(frame) => frame,
+                            Err(e) => {
+                                error!("Дисплей {}: {}", display.index, e);
+                                colors.extend_from_slice(&display.colors);
+                                continue;
+                            }
+                        };
+                    let dither = self.dithering.then_some(&mut display.dither_residuals);
+                    let table = self.integral_image.then_some(&mut display.summed_area_table);
+                    display.colors =
+                        color::compute_colors(frame, stride, &display.regions, sampling, pipeline, dither, table);
+                    color::apply_side_brightness(&mut display.colors, &display.regions, |side| {
+                        display.config.side_brightness(side)
+                    });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    error!("Ошибка захвата дисплея {}: {}", display.index, e);
+                    match open_display(display.index) {
+                        Ok(capturer) => {
+                            // Число светодиодов не меняется, меняются только регионы
+                            if (capturer.width(), capturer.height()) != (width, height) {
+                                display.regions =
+                                    create_led_regions(&display.config, capturer.width(), capturer.height());
+                            }
+                            display.capturer = capturer;
+                        }
+                        Err(e) => error!("Не удалось пересоздать захват дисплея {}: {}", display.index, e),
+                    }
+                }
+            }
+            colors.extend_from_slice(&display.colors);
+        }
+    }
+}
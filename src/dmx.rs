@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+use std::net::UdpSocket;
+use std::ops::RangeInclusive;
+
+/// Светодиодов в одном DMX-универсуме: 170 * 3 = 510 из 512 каналов.
+pub const LEDS_PER_UNIVERSE: usize = 170;
+const CHANNELS_PER_UNIVERSE: usize = LEDS_PER_UNIVERSE * 3;
+
+/// Стандартные порты протоколов.
+pub const ARTNET_PORT: u16 = 6454;
+pub const SACN_PORT: u16 = 5568;
+
+/// Идентификатор источника sACN (CID), постоянный для программы.
+const SACN_CID: [u8; 16] = *b"Ambilight-sACN-1";
+const SACN_SOURCE_NAME: &[u8] = b"Ambilight";
+
+/// Протокол передачи DMX по сети.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DmxProtocol {
+    ArtNet,
+    Sacn,
+}
+
+impl DmxProtocol {
+    /// Допустимые номера универсумов: в Art-Net — 15 бит, в sACN по E1.31 — 1–63999.
+    fn universes(self) -> RangeInclusive<usize> {
+        match self {
+            DmxProtocol::ArtNet => 0..=32767,
+            DmxProtocol::Sacn => 1..=63999,
+        }
+    }
+}
+
+/// Проверяет, что `led_count` светодиодов, начиная с `start_universe`, помещаются
+/// в допустимые для протокола универсумы.
+pub fn validate_universes(protocol: DmxProtocol, start_universe: u16, led_count: usize) -> Result<(), String> {
+    let universes = protocol.universes();
+    if !universes.contains(&(start_universe as usize)) {
+        return Err(format!(
+            "dmx_universe = {} вне диапазона {}–{} для {:?}",
+            start_universe,
+            universes.start(),
+            universes.end(),
+            protocol
+        ));
+    }
+    let last = start_universe as usize + led_count.div_ceil(LEDS_PER_UNIVERSE).max(1) - 1;
+    if last > *universes.end() {
+        return Err(format!(
+            "{} светодиодов с dmx_universe = {} доходят до универсума {}, а последний в {:?} — {}",
+            led_count,
+            start_universe,
+            last,
+            protocol,
+            universes.end()
+        ));
+    }
+    Ok(())
+}
+
+/// Пакет ArtDmx с данными одного универсума.
+fn build_artnet_packet(universe: u16, sequence: u8, data: &[u8], buf: &mut Vec<u8>) {
+    // Длина данных в Art-Net должна быть чётной
+    let length = data.len() + data.len() % 2;
+    buf.clear();
+    buf.extend_from_slice(b"Art-Net\0");
+    buf.extend_from_slice(&0x5000u16.to_le_bytes()); // OpDmx
+    buf.extend_from_slice(&14u16.to_be_bytes()); // версия протокола
+    buf.push(sequence);
+    buf.push(0); // физический порт
+    buf.extend_from_slice(&[(universe & 0xFF) as u8, (universe >> 8) as u8 & 0x7F]);
+    buf.extend_from_slice(&(length as u16).to_be_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(18 + length, 0);
+}
+
+/// Пакет E1.31 (sACN) с данными одного универсума.
+fn build_sacn_packet(universe: u16, sequence: u8, data: &[u8], buf: &mut Vec<u8>) {
+    let total = 126 + data.len();
+    // Поле «флаги и длина» каждого уровня: 0x7 в старших битах и длина до конца пакета
+    let flags_and_length = |from: usize| (0x7000 | (total - from) as u16).to_be_bytes();
+
+    buf.clear();
+    // Корневой уровень
+    buf.extend_from_slice(&0x0010u16.to_be_bytes());
+    buf.extend_from_slice(&0x0000u16.to_be_bytes());
+    buf.extend_from_slice(b"ASC-E1.17\0\0\0");
+    buf.extend_from_slice(&flags_and_length(16));
+    buf.extend_from_slice(&0x0000_0004u32.to_be_bytes());
+    buf.extend_from_slice(&SACN_CID);
+    // Уровень кадра
+    buf.extend_from_slice(&flags_and_length(38));
+    buf.extend_from_slice(&0x0000_0002u32.to_be_bytes());
+    let mut source_name = [0u8; 64];
+    source_name[..SACN_SOURCE_NAME.len()].copy_from_slice(SACN_SOURCE_NAME);
+    buf.extend_from_slice(&source_name);
+    buf.push(100); // приоритет
+    buf.extend_from_slice(&0u16.to_be_bytes()); // адрес синхронизации
+    buf.push(sequence);
+    buf.push(0); // опции
+    buf.extend_from_slice(&universe.to_be_bytes());
+    // Уровень DMP
+    buf.extend_from_slice(&flags_and_length(115));
+    buf.push(0x02);
+    buf.push(0xA1);
+    buf.extend_from_slice(&0u16.to_be_bytes()); // адрес первого свойства
+    buf.extend_from_slice(&1u16.to_be_bytes()); // шаг адреса
+    buf.extend_from_slice(&(data.len() as u16 + 1).to_be_bytes());
+    buf.push(0); // стартовый код DMX
+    buf.extend_from_slice(data);
+}
+
+/// Вывод на DMX-контроллер: принимает подряд идущие тройки R, G, B и при каждом `write`
+/// раскладывает их по универсумам, начиная с `start_universe`, по одному UDP-пакету на универсум.
+pub struct DmxUdp {
+    socket: UdpSocket,
+    protocol: DmxProtocol,
+    start_universe: u16,
+    sequence: u8,
+    packet: Vec<u8>,
+}
+
+impl DmxUdp {
+    pub fn connect(protocol: DmxProtocol, address: &str, port: u16, start_universe: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((address, port))?;
+        Ok(DmxUdp { socket, protocol, start_universe, sequence: 0, packet: Vec::new() })
+    }
+}
+
+impl Write for DmxUdp {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Нулевой номер последовательности в обоих протоколах означает «не используется»
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+        for (i, data) in buf.chunks(CHANNELS_PER_UNIVERSE).enumerate() {
+            let universe = u16::try_from(i)
+                .ok()
+                .and_then(|i| self.start_universe.checked_add(i))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "номер универсума больше 65535"))?;
+            match self.protocol {
+                DmxProtocol::ArtNet => build_artnet_packet(universe, self.sequence, data, &mut self.packet),
+                DmxProtocol::Sacn => build_sacn_packet(universe, self.sequence, data, &mut self.packet),
+            }
+            self.socket.send(&self.packet)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artnet_header_and_even_length() {
+        let mut buf = Vec::new();
+        build_artnet_packet(0x0102, 7, &[1, 2, 3], &mut buf);
+        assert_eq!(&buf[..8], b"Art-Net\0");
+        assert_eq!(&buf[8..18], [0x00, 0x50, 0, 14, 7, 0, 0x02, 0x01, 0, 4]);
+        assert_eq!(&buf[18..], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn universes_must_fit_protocol_range() {
+        assert!(validate_universes(DmxProtocol::ArtNet, 0, 170).is_ok());
+        assert!(validate_universes(DmxProtocol::ArtNet, 32767, 170).is_ok());
+        assert!(validate_universes(DmxProtocol::ArtNet, 32767, 171).is_err());
+        assert!(validate_universes(DmxProtocol::ArtNet, 32768, 0).is_err());
+        assert!(validate_universes(DmxProtocol::Sacn, 0, 10).is_err());
+        assert!(validate_universes(DmxProtocol::Sacn, 63998, 340).is_ok());
+        assert!(validate_universes(DmxProtocol::Sacn, 63998, 341).is_err());
+    }
+
+    #[test]
+    fn sacn_layer_lengths_and_universe() {
+        let data = [9u8; 510];
+        let mut buf = Vec::new();
+        build_sacn_packet(3, 1, &data, &mut buf);
+        assert_eq!(buf.len(), 126 + 510);
+        assert_eq!(&buf[16..18], (0x7000u16 | (buf.len() - 16) as u16).to_be_bytes());
+        assert_eq!(&buf[113..115], [0, 3]);
+        assert_eq!(&buf[123..126], [0x01, 0xFF, 0]);
+        assert_eq!(&buf[126..], data);
+    }
+}
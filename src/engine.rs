@@ -0,0 +1,597 @@
+use crate::color::{self, ColorMode, Pipeline, PipelineStages, Sampling, SummedAreaTable};
+use crate::config::AmbilightConfig;
+use crate::contrast::AutoContrast;
+use crate::control::{Mode, RuntimeState};
+use crate::idle::{self, IdleDimmer, IdleSettings};
+use crate::import::{self, NormalizedRegion};
+use crate::letterbox::{self, LetterboxDetector};
+use crate::mirror;
+use crate::packet::{self, ChannelLayout};
+use crate::power::{self, PowerModel};
+use crate::profile::{ContentDetector, DetectorThresholds, Profile};
+use crate::regions::{CaptureArea, Layout, LedRegion, create_led_regions, create_matrix_regions};
+use crate::remap;
+use crate::schedule;
+use crate::segments::SegmentCorrection;
+use crate::smoothing::{self, Deadband, Smoother};
+use crate::status::{self, EngineStatus, StatusColors};
+use log::{info, warn};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// Создаёт регионы для выбранной раскладки с учётом направления ленты.
+/// Регионы строятся внутри `area` и возвращаются в координатах всего кадра.
+/// `imported` — раскладка, загруженная из файла (для импортируемых раскладок).
+pub fn build_led_regions(
+    config: &AmbilightConfig,
+    imported: &[NormalizedRegion],
+    area: CaptureArea,
+) -> Vec<LedRegion> {
+    let CaptureArea { width, height, .. } = area;
+    // Основной цикл захватывает первый из `[[displays]]`, со своим числом светодиодов
+    let display_config;
+    let config = match config.displays.first() {
+        Some(display) => {
+            display_config = display.apply_to(config);
+            &display_config
+        }
+        None => config,
+    };
+    let mut regions = match config.layout {
+        Layout::Edges => create_led_regions(config, width, height),
+        Layout::Matrix => create_matrix_regions(
+            config.matrix_columns,
+            config.matrix_rows,
+            config.matrix_wiring,
+            width,
+            height,
+        ),
+        Layout::Hyperion => import::to_pixel_regions(imported, width, height),
+    };
+    for region in &mut regions {
+        region.x1 += area.x;
+        region.x2 += area.x;
+        region.y1 += area.y;
+        region.y2 += area.y;
+    }
+    // С несколькими дисплеями разворачивается вся лента целиком, уже после склейки цветов
+    if config.invert_direction && config.displays.len() <= 1 {
+        regions.reverse();
+    }
+    regions
+}
+
+/// Загружает импортируемую раскладку, если она выбрана в настройках.
+pub fn load_imported_layout(config: &AmbilightConfig) -> Result<Vec<NormalizedRegion>, Box<dyn Error>> {
+    match (config.layout, &config.hyperion_layout_file) {
+        (Layout::Hyperion, Some(path)) => Ok(import::load_hyperion_layout(path)?),
+        (Layout::Hyperion, None) => Err("Для layout = \"hyperion\" нужен hyperion_layout_file".into()),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Параметры цветокоррекции по настройкам (множители баланса белого считаются один раз).
+pub fn build_pipeline(config: &AmbilightConfig) -> Pipeline {
+    let mut pipeline = Pipeline::new(
+        config.channel_gamma(config.gamma),
+        config.saturation,
+        config.white_balance_temperature,
+        (config.brightness as f32) / 100.0,
+        PipelineStages {
+            gamma: config.gamma_enabled,
+            white_balance: config.white_balance_enabled,
+            brightness: config.brightness_enabled,
+            smoothing: config.smoothing_enabled,
+        },
+    );
+    pipeline.set_color_matrix(config.color_matrix);
+    pipeline.set_soft_clip(config.soft_clip);
+    pipeline.set_white_balance_before_gamma(config.white_balance_before_gamma);
+    pipeline
+}
+
+/// Как выбираются пиксели регионов по настройкам.
+pub fn sampling(config: &AmbilightConfig) -> Sampling {
+    Sampling {
+        step: config.sample_step,
+        trim_percent: config.region_trim_percent,
+        edge_falloff: config.edge_weight_falloff,
+        linear: config.linear_blend,
+        dominant: config.color_mode == ColorMode::Dominant,
+    }
+}
+
+/// Применяет профиль изображения к цветокоррекции поверх общих настроек.
+/// Возвращает множитель яркости профиля и силу сглаживания.
+fn apply_profile(config: &AmbilightConfig, profile: &Profile, pipeline: &mut Pipeline) -> (f32, f32) {
+    pipeline.set_gamma(config.channel_gamma(profile.gamma.unwrap_or(config.gamma)));
+    pipeline.set_white_balance(
+        profile.white_balance_temperature.unwrap_or(config.white_balance_temperature),
+    );
+    let brightness = profile.brightness.map_or(1.0, |brightness| brightness as f32 / 100.0);
+    (brightness, profile.smoothing.unwrap_or(config.smoothing))
+}
+
+/// Детектор типа изображения для автовыбора профиля, если он включён.
+fn build_content_detector(config: &AmbilightConfig) -> Option<ContentDetector> {
+    config.auto_profile.then(|| {
+        ContentDetector::new(DetectorThresholds {
+            motion: config.profile_motion_threshold,
+            saturation: config.profile_saturation_threshold,
+            hysteresis: config.profile_hysteresis,
+            hold: Duration::from_secs(config.profile_hold_secs),
+        })
+    })
+}
+
+/// Перечитанные настройки с уже построенными регионами: по ним до применения
+/// проверяется число светодиодов. Получаются из [`Ambilight::prepare`].
+pub struct PreparedConfig {
+    config: AmbilightConfig,
+    imported: Vec<NormalizedRegion>,
+    capture_area: CaptureArea,
+    regions: Vec<LedRegion>,
+}
+
+impl PreparedConfig {
+    pub fn config(&self) -> &AmbilightConfig {
+        &self.config
+    }
+
+    /// Светодиодов в регионах движка, без дополнительных дисплеев.
+    pub fn led_count(&self) -> usize {
+        self.regions.len()
+    }
+}
+
+/// Движок без захвата и вывода: из кадра BGRA получает цвета ленты и пакет Adalight.
+/// На нём работает и основной цикл, и его можно встроить в другие программы, которые
+/// сами захватывают экран и пишут в порт.
+///
+/// [`Ambilight::sample_frame`] усредняет регионы с цветокоррекцией, выбирает профиль
+/// изображения и применяет зеркальный режим и яркость сторон; [`Ambilight::process_colors`]
+/// добавляет коррекцию сегментов, сглаживание, автоуровни, мёртвую зону, минимальную
+/// яркость, фильтр вывода, затухание, статусный светодиод, плавный старт и ограничение тока.
+pub struct Ambilight {
+    config: AmbilightConfig,
+    width: usize,
+    height: usize,
+    imported: Vec<NormalizedRegion>,
+    capture_area: CaptureArea,
+    letterbox_detector: Option<LetterboxDetector>,
+    regions: Vec<LedRegion>,
+    pipeline: Pipeline,
+    layout: ChannelLayout,
+    /// Режим, яркость и этапы обработки, которые меняет сервер управления
+    state: RuntimeState,
+    /// Итоговая яркость текущего кадра: из `state`, профиля и расписания
+    brightness: f32,
+    segment_corrections: Vec<SegmentCorrection>,
+    /// Автовыбор профиля изображения: начинаем с профиля «фильм»
+    content_detector: Option<ContentDetector>,
+    profile_brightness: f32,
+    smoothing: f32,
+    smoother: Smoother,
+    deadband: Deadband,
+    auto_contrast: AutoContrast,
+    idle_dimmer: IdleDimmer,
+    /// Накопленные остатки дизеринга каждого светодиода
+    dither_residuals: Vec<[u8; 3]>,
+    /// Таблица сумм кадра для integral_image
+    summed_area_table: SummedAreaTable,
+    /// Пиксели под регионами и рассчитанные по ним цвета для пропуска одинаковых кадров
+    region_snapshot: Vec<u8>,
+    previous_snapshot: Vec<u8>,
+    screen_colors: Vec<(u8, u8, u8)>,
+    skipped_frames: usize,
+    /// Последняя отправка на ленту не удалась, для статусного светодиода
+    output_error: bool,
+    /// Первый обработанный кадр, для плавного старта и анимаций
+    started: Option<Instant>,
+    /// Когда обработан прошлый кадр, для `smoothing_time_ms` и затухания
+    last_frame: Option<Instant>,
+}
+
+impl Ambilight {
+    /// Готовит движок для кадров `width` x `height`. Настройки проверяются так же,
+    /// как при чтении файла.
+    pub fn new(config: AmbilightConfig, width: usize, height: usize) -> Result<Self, Box<dyn Error>> {
+        config.validate()?;
+        let imported = load_imported_layout(&config)?;
+        let capture_area = config.capture_area(width, height);
+        let regions = build_led_regions(&config, &imported, capture_area);
+        // С несколькими дисплеями лента длиннее регионов движка: её проверяет вызывающий
+        if config.displays.len() <= 1 {
+            remap::validate_remap(&config.led_remap, regions.len())?;
+        }
+        let mut pipeline = build_pipeline(&config);
+        let content_detector = build_content_detector(&config);
+        let (profile_brightness, smoothing) = match &content_detector {
+            Some(detector) => apply_profile(&config, config.profiles.get(detector.current()), &mut pipeline),
+            None => (1.0, config.smoothing),
+        };
+        let state = RuntimeState {
+            mode: Mode::Screen,
+            brightness: pipeline.brightness(),
+            static_color: config.static_color,
+            stages: pipeline.stages(),
+            paused: false,
+        };
+        let mut engine = Ambilight {
+            width,
+            height,
+            imported,
+            capture_area,
+            letterbox_detector: config
+                .detect_letterbox
+                .then(|| LetterboxDetector::new(config.letterbox_interval_frames)),
+            regions,
+            layout: config.channel_layout()?,
+            state,
+            brightness: 1.0,
+            segment_corrections: config.segments.iter().map(SegmentCorrection::new).collect(),
+            content_detector,
+            profile_brightness,
+            smoothing,
+            pipeline,
+            smoother: Smoother::default(),
+            deadband: Deadband::default(),
+            auto_contrast: AutoContrast::default(),
+            idle_dimmer: IdleDimmer::default(),
+            dither_residuals: Vec::new(),
+            summed_area_table: SummedAreaTable::default(),
+            region_snapshot: Vec::new(),
+            previous_snapshot: Vec::new(),
+            screen_colors: Vec::new(),
+            skipped_frames: 0,
+            output_error: false,
+            started: None,
+            last_frame: None,
+            config,
+        };
+        engine.brightness = engine.current_brightness();
+        Ok(engine)
+    }
+
+    /// Регионы кадра в порядке светодиодов.
+    pub fn regions(&self) -> &[LedRegion] {
+        &self.regions
+    }
+
+    /// Цветокоррекция текущего профиля; ею же считаются дополнительные дисплеи.
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// Прямоугольник экрана, по краям которого строятся регионы.
+    pub fn capture_area(&self) -> CaptureArea {
+        self.capture_area
+    }
+
+    /// Режим, яркость и этапы обработки; после создания и [`Ambilight::reconfigure`] — из настроек.
+    pub fn state(&self) -> RuntimeState {
+        self.state
+    }
+
+    /// Задаёт режим, яркость и этапы обработки, например, полученные сервером управления.
+    pub fn set_state(&mut self, state: RuntimeState) {
+        self.state = state;
+    }
+
+    /// Сообщает, удалась ли последняя отправка на ленту: это показывает статусный светодиод.
+    pub fn set_output_error(&mut self, error: bool) {
+        self.output_error = error;
+    }
+
+    /// Кадров, которые с прошлого вызова не пересчитывались при `skip_identical_frames`.
+    pub fn take_skipped_frames(&mut self) -> usize {
+        std::mem::take(&mut self.skipped_frames)
+    }
+
+    /// Яркость из `state` с учётом профиля и расписания по времени суток.
+    fn current_brightness(&self) -> f32 {
+        self.state.brightness * self.profile_brightness * schedule::current_brightness(&self.config.brightness_schedule)
+    }
+
+    /// Перестраивает регионы по области захвата и найденным чёрным полосам.
+    fn rebuild_regions(&mut self) {
+        let bar = self.letterbox_detector.as_ref().map_or(0, LetterboxDetector::bar);
+        self.regions =
+            build_led_regions(&self.config, &self.imported, letterbox::inner_area(self.capture_area, bar));
+        self.previous_snapshot.clear();
+    }
+
+    /// Переносит регионы в новую область захвата, например, на активное окно.
+    pub fn set_capture_area(&mut self, area: CaptureArea) {
+        self.capture_area = area;
+        self.rebuild_regions();
+    }
+
+    /// Новое разрешение экрана: область захвата и регионы строятся заново, найденные
+    /// чёрные полосы сбрасываются.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        (self.width, self.height) = (width, height);
+        self.capture_area = self.config.capture_area(width, height);
+        if let Some(detector) = &mut self.letterbox_detector {
+            detector.reset();
+        }
+        self.rebuild_regions();
+    }
+
+    /// Готовит перечитанные настройки: загружает раскладку и строит регионы в области
+    /// `area` (по умолчанию — `capture_*` из новых настроек), но ещё не применяет их.
+    pub fn prepare(
+        &self,
+        config: AmbilightConfig,
+        area: Option<CaptureArea>,
+    ) -> Result<PreparedConfig, Box<dyn Error>> {
+        let imported = load_imported_layout(&config)?;
+        let capture_area = area.unwrap_or_else(|| config.capture_area(self.width, self.height));
+        let bar = self.letterbox_detector.as_ref().map_or(0, LetterboxDetector::bar);
+        let regions = build_led_regions(&config, &imported, letterbox::inner_area(capture_area, bar));
+        Ok(PreparedConfig { config, imported, capture_area, regions })
+    }
+
+    /// Применяет настройки из [`Ambilight::prepare`]. Цветокоррекция, профиль и яркость
+    /// в `state` берутся из них заново, а сглаживание продолжается с прежних цветов.
+    pub fn reconfigure(&mut self, prepared: PreparedConfig) {
+        let PreparedConfig { config, imported, capture_area, regions } = prepared;
+        if config.detect_letterbox != self.letterbox_detector.is_some() {
+            self.letterbox_detector =
+                config.detect_letterbox.then(|| LetterboxDetector::new(config.letterbox_interval_frames));
+        }
+        self.segment_corrections = config.segments.iter().map(SegmentCorrection::new).collect();
+        self.pipeline = build_pipeline(&config);
+        self.content_detector = build_content_detector(&config);
+        (self.profile_brightness, self.smoothing) = match &self.content_detector {
+            Some(detector) => apply_profile(&config, config.profiles.get(detector.current()), &mut self.pipeline),
+            None => (1.0, config.smoothing),
+        };
+        // Настройки уже проверены при чтении
+        self.layout = config.channel_layout().unwrap_or_default();
+        self.state.brightness = self.pipeline.brightness();
+        self.state.static_color = config.static_color;
+        self.state.stages = self.pipeline.stages();
+        self.previous_snapshot.clear();
+        (self.config, self.imported, self.capture_area, self.regions) = (config, imported, capture_area, regions);
+    }
+
+    /// Цвета светодиодов по кадру BGRA; строки кадра могут быть выровнены.
+    pub fn process_frame(&mut self, frame: &[u8]) -> Vec<(u8, u8, u8)> {
+        let colors = self.sample_frame(frame);
+        self.process_colors(colors)
+    }
+
+    /// Первая половина [`Ambilight::process_frame`]: цвета регионов после цветокоррекции,
+    /// выбора профиля, зеркального режима и яркости сторон. Кадр, в который регионы
+    /// не помещаются (другого размера), даёт чёрные цвета.
+    pub fn sample_frame(&mut self, frame: &[u8]) -> Vec<(u8, u8, u8)> {
+        self.brightness = self.current_brightness();
+        let stride = frame.len() / self.height.max(1);
+        if !color::regions_fit(frame.len(), stride, &self.regions) {
+            warn!("Кадр ({} байт) меньше, чем нужно регионам, цвета не рассчитаны", frame.len());
+            return vec![(0, 0, 0); self.regions.len()];
+        }
+        // Полосы letterbox: регионы сдвигаются внутрь, к самому изображению
+        if let Some(bar) = self
+            .letterbox_detector
+            .as_mut()
+            .and_then(|detector| detector.update(frame, stride, self.capture_area))
+        {
+            info!("Чёрные полосы: {} пикс.", bar);
+            self.rebuild_regions();
+        }
+        let unchanged = if self.config.skip_identical_frames {
+            color::snapshot_regions(frame, stride, &self.regions, &mut self.region_snapshot);
+            let unchanged = self.region_snapshot == self.previous_snapshot
+                && self.pipeline.brightness() == self.brightness
+                && self.pipeline.stages() == self.state.stages
+                && self.screen_colors.len() == self.regions.len();
+            std::mem::swap(&mut self.region_snapshot, &mut self.previous_snapshot);
+            unchanged
+        } else {
+            false
+        };
+        if unchanged {
+            self.skipped_frames += 1;
+            return self.screen_colors.clone();
+        }
+
+        let config = &self.config;
+        self.pipeline.set_brightness(self.brightness);
+        self.pipeline.set_stages(self.state.stages);
+        self.screen_colors = color::compute_colors(
+            frame,
+            stride,
+            &self.regions,
+            sampling(config),
+            &self.pipeline,
+            config.dithering.then_some(&mut self.dither_residuals),
+            config.integral_image.then_some(&mut self.summed_area_table),
+        );
+        if let Some(kind) = self.content_detector.as_mut().and_then(|detector| detector.update(&self.screen_colors)) {
+            info!("Профиль изображения: {}", kind);
+            (self.profile_brightness, self.smoothing) =
+                apply_profile(config, config.profiles.get(kind), &mut self.pipeline);
+            self.previous_snapshot.clear();
+        }
+        mirror::apply_mirror(&mut self.screen_colors, &self.regions, config.mirror_mode);
+        color::apply_side_brightness(&mut self.screen_colors, &self.regions, |side| config.side_brightness(side));
+        self.screen_colors.clone()
+    }
+
+    /// Цвета `led_count` светодиодов без захвата экрана: заливка `static_color`
+    /// в режиме [`Mode::Static`], иначе чёрный. Передаются в [`Ambilight::process_colors`].
+    pub fn fill_colors(&mut self, led_count: usize) -> Vec<(u8, u8, u8)> {
+        self.brightness = self.current_brightness();
+        if self.state.mode != Mode::Static {
+            return vec![(0, 0, 0); led_count];
+        }
+        let (r, g, b) = self.state.static_color;
+        let brightness = if self.state.stages.brightness { self.brightness } else { 1.0 };
+        let scale = |c: u8| (c as f32 * brightness).min(255.0) as u8;
+        vec![(scale(r), scale(g), scale(b)); led_count]
+    }
+
+    /// Вторая половина [`Ambilight::process_frame`]: сегменты, сглаживание и остальные шаги
+    /// над цветами всей ленты из [`Ambilight::sample_frame`] или [`Ambilight::fill_colors`].
+    pub fn process_colors(&mut self, mut colors: Vec<(u8, u8, u8)>) -> Vec<(u8, u8, u8)> {
+        let config = &self.config;
+        let RuntimeState { mode, static_color, stages, .. } = self.state;
+        let brightness = self.brightness;
+        for correction in &self.segment_corrections {
+            correction.apply(&mut colors);
+        }
+
+        let now = Instant::now();
+        let started = *self.started.get_or_insert(now);
+        let frame_elapsed = self.last_frame.map_or(Duration::ZERO, |last_frame| now - last_frame);
+        self.last_frame = Some(now);
+        // С постоянной времени сила сглаживания считается по реальной длительности кадра
+        let smoothing = match config.smoothing_time_ms {
+            Some(ms) => smoothing::smoothing_for_interval(Duration::from_millis(ms), frame_elapsed),
+            None => self.smoothing,
+        };
+        if stages.smoothing {
+            self.smoother.antialias(&mut colors, config.temporal_antialiasing);
+            match config.adaptive_smoothing() {
+                Some(adaptive) => self.smoother.apply_adaptive(&mut colors, adaptive, config.linear_smoothing),
+                None => self.smoother.apply(&mut colors, smoothing, config.linear_smoothing),
+            }
+        } else {
+            self.smoother.apply(&mut colors, 0.0, config.linear_smoothing);
+        }
+        if mode == Mode::Screen
+            && let Some(settings) = config.contrast_settings()
+        {
+            self.auto_contrast.apply(&mut colors, &settings);
+        }
+        self.deadband.apply(&mut colors, config.deadband);
+        if mode == Mode::Screen {
+            color::apply_min_brightness(&mut colors, config.min_brightness);
+        }
+        color::apply_output_filter(&mut colors, config.output_filter);
+
+        // Затухание при долго неподвижной картинке (до статусного светодиода, чтобы
+        // его мигание не считалось движением). С idle_animation картинка не гаснет,
+        // а переходит в анимацию с той же яркостью
+        if let Some(timeout) = config.idle_timeout_secs {
+            let settings = IdleSettings {
+                timeout: Duration::from_secs(timeout),
+                threshold: config.idle_threshold,
+                fade_out: Duration::from_secs_f32(config.idle_fade_out_secs),
+                fade_in: Duration::from_secs_f32(config.idle_fade_in_secs),
+            };
+            let factor = self.idle_dimmer.update(&colors, frame_elapsed, &settings);
+            if factor < 1.0 {
+                let animation =
+                    idle::animation_frame(config.idle_animation, colors.len(), started.elapsed(), static_color);
+                let animation_brightness = if stages.brightness { brightness * (1.0 - factor) } else { 1.0 - factor };
+                let blend = |c: u8, a: u8| (c as f32 * factor + a as f32 * animation_brightness).min(255.0) as u8;
+                for ((r, g, b), (ar, ag, ab)) in colors.iter_mut().zip(animation) {
+                    *r = blend(*r, ar);
+                    *g = blend(*g, ag);
+                    *b = blend(*b, ab);
+                }
+            }
+        }
+
+        if let Some(led) = config.status_led_index.and_then(|index| colors.get_mut(index)) {
+            let status = if self.output_error {
+                EngineStatus::SerialError
+            } else if mode == Mode::Screen {
+                EngineStatus::Active
+            } else {
+                EngineStatus::Idle
+            };
+            let status_colors = StatusColors {
+                idle: config.status_led_idle_color,
+                active: config.status_led_active_color,
+                error: config.status_led_error_color,
+            };
+            *led = status::status_color(status, started.elapsed(), &status_colors);
+        }
+
+        // Плавный старт: все светодиоды разом на полную яркость могут перегрузить блок питания
+        if config.soft_start_ms > 0 {
+            let ramp = started.elapsed().as_secs_f32() * 1000.0 / config.soft_start_ms as f32;
+            if ramp < 1.0 {
+                for (r, g, b) in colors.iter_mut() {
+                    *r = (*r as f32 * ramp) as u8;
+                    *g = (*g as f32 * ramp) as u8;
+                    *b = (*b as f32 * ramp) as u8;
+                }
+            }
+        }
+
+        // Ограничение тока: последним шагом перед пакетом, чтобы учитывались все светодиоды
+        if let Some(max_ma) = config.max_power_ma {
+            let model = PowerModel { channel_ma: config.led_channel_ma, idle_ma: config.led_idle_ma };
+            power::limit_power(&mut colors, model, max_ma as f32);
+        }
+        colors
+    }
+
+    /// Пакет Adalight для цветов с заголовком, порядком байт и `led_remap` из настроек.
+    pub fn build_packet(&self, colors: &[(u8, u8, u8)]) -> Vec<u8> {
+        let mut remapped = Vec::new();
+        let colors = if self.config.led_remap.is_empty() {
+            colors
+        } else {
+            remap::apply_remap(colors, &self.config.led_remap, &mut remapped);
+            &remapped
+        };
+        let mut buf = Vec::new();
+        packet::build_adalight_packet_with(colors, self.layout, &mut buf);
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CONFIG;
+
+    #[test]
+    fn engine_turns_frame_into_packet() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.displays.clear();
+        let (width, height) = (64, 36);
+        let mut engine = Ambilight::new(config, width, height).unwrap();
+        let frame = vec![0u8; width * height * color::BYTES_PER_PIXEL];
+        let colors = engine.process_frame(&frame);
+        assert_eq!(colors.len(), engine.regions().len());
+
+        let packet = engine.build_packet(&colors);
+        let mut expected = Vec::new();
+        packet::build_adalight_packet(&colors, &mut expected);
+        assert_eq!(packet, expected);
+    }
+
+    #[test]
+    fn static_mode_fills_strip_and_survives_reconfigure() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        config.displays.clear();
+        config.auto_profile = false;
+        config.brightness_schedule.clear();
+        let mut engine = Ambilight::new(config.clone(), 64, 36).unwrap();
+        engine.set_state(RuntimeState {
+            mode: Mode::Static,
+            brightness: 0.5,
+            static_color: (200, 100, 50),
+            ..engine.state()
+        });
+        assert_eq!(engine.fill_colors(3), vec![(100, 50, 25); 3]);
+
+        // Новые настройки возвращают яркость и цвет, но не режим
+        config.static_color = (10, 20, 30);
+        let prepared = engine.prepare(config, None).unwrap();
+        assert_eq!(prepared.led_count(), engine.regions().len());
+        engine.reconfigure(prepared);
+        assert_eq!(engine.state().mode, Mode::Static);
+        assert_eq!(engine.state().static_color, (10, 20, 30));
+
+        engine.set_state(RuntimeState { mode: Mode::Off, ..engine.state() });
+        assert_eq!(engine.fill_colors(2), vec![(0, 0, 0); 2]);
+    }
+}
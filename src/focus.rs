@@ -0,0 +1,90 @@
+use crate::regions::CaptureArea;
+use std::time::{Duration, Instant};
+
+/// Какую долю оставшегося пути область захвата проходит за кадр при смене окна.
+const FOCUS_SMOOTHING: f32 = 0.2;
+/// Окна меньше этого размера (свёрнутые, служебные) игнорируются.
+const MIN_WINDOW_SIZE: i32 = 16;
+
+/// Границы активного окна в пикселях основного дисплея: (left, top, right, bottom).
+#[cfg(windows)]
+fn focused_window_bounds() -> Option<(i32, i32, i32, i32)> {
+    use windows_sys::Win32::Foundation::RECT;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect};
+
+    let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+    // SAFETY: GetWindowRect пишет только в переданную структуру RECT
+    let found = unsafe {
+        let window = GetForegroundWindow();
+        !window.is_null() && GetWindowRect(window, &mut rect) != 0
+    };
+    found.then_some((rect.left, rect.top, rect.right, rect.bottom))
+}
+
+/// Вне Windows активное окно не отслеживается: захват остаётся на весь экран.
+#[cfg(not(windows))]
+fn focused_window_bounds() -> Option<(i32, i32, i32, i32)> {
+    None
+}
+
+/// Область активного окна, обрезанная по экрану.
+fn focused_window_area(width: usize, height: usize) -> Option<CaptureArea> {
+    let (left, top, right, bottom) = focused_window_bounds()?;
+    let x1 = left.clamp(0, width as i32);
+    let y1 = top.clamp(0, height as i32);
+    let x2 = right.clamp(0, width as i32);
+    let y2 = bottom.clamp(0, height as i32);
+    if x2 - x1 < MIN_WINDOW_SIZE || y2 - y1 < MIN_WINDOW_SIZE {
+        return None;
+    }
+    Some(CaptureArea {
+        x: x1 as usize,
+        y: y1 as usize,
+        width: (x2 - x1) as usize,
+        height: (y2 - y1) as usize,
+    })
+}
+
+/// Следит за активным окном и плавно переводит на него область захвата.
+pub struct FocusTracker {
+    interval: Duration,
+    last_poll: Option<Instant>,
+    target: [f32; 4],
+    current: [f32; 4],
+    area: CaptureArea,
+}
+
+impl FocusTracker {
+    pub fn new(interval: Duration, area: CaptureArea) -> Self {
+        let rect = to_rect(&area);
+        FocusTracker { interval, last_poll: None, target: rect, current: rect, area }
+    }
+
+    /// Раз в `interval` опрашивает активное окно и сдвигает область захвата к нему.
+    /// Возвращает новую область, если она изменилась хотя бы на пиксель.
+    pub fn update(&mut self, width: usize, height: usize) -> Option<CaptureArea> {
+        if self.last_poll.is_none_or(|last| last.elapsed() >= self.interval) {
+            self.last_poll = Some(Instant::now());
+            let area = focused_window_area(width, height)
+                .unwrap_or(CaptureArea { x: 0, y: 0, width, height });
+            self.target = to_rect(&area);
+        }
+
+        for (current, target) in self.current.iter_mut().zip(self.target) {
+            let step = (target - *current) * FOCUS_SMOOTHING;
+            // Последний пиксель проходим сразу, иначе приближение никогда не закончится
+            *current = if step.abs() < 0.5 { target } else { *current + step };
+        }
+        let [x, y, w, h] = self.current.map(|value| value.round() as usize);
+        let area = CaptureArea { x, y, width: w, height: h };
+        if area == self.area {
+            return None;
+        }
+        self.area = area;
+        Some(area)
+    }
+}
+
+fn to_rect(area: &CaptureArea) -> [f32; 4] {
+    [area.x as f32, area.y as f32, area.width as f32, area.height as f32]
+}
@@ -0,0 +1,146 @@
+use crate::color::{BYTES_PER_PIXEL, CHANNEL_A, CHANNEL_B, CHANNEL_G, CHANNEL_R};
+use serde::{Deserialize, Serialize};
+
+/// Формат пикселей, который отдаёт захват экрана.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PixelFormat {
+    /// Байты B, G, R, A (DXGI и большинство захватов scrap)
+    #[default]
+    Bgra,
+    /// Байты R, G, B, A
+    Rgba,
+    /// Байты X, R, G, B (первый байт не используется)
+    Xrgb,
+    /// 16 бит на пиксель, little-endian: 5 бит R, 6 бит G, 5 бит B
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            _ => 4,
+        }
+    }
+}
+
+/// Переводит кадр в формат BGRA с плотными строками (`width * 4` байт) в буфер `out`.
+/// Размер строки исходного кадра определяется как `frame.len() / height`.
+pub fn convert_to_bgra(
+    frame: &[u8],
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), String> {
+    let bytes_per_pixel = format.bytes_per_pixel();
+    let stride = frame.len() / height.max(1);
+    if stride < width * bytes_per_pixel {
+        return Err(format!(
+            "Кадр размером {} байт не подходит под формат {:?} для экрана {}x{}",
+            frame.len(),
+            format,
+            width,
+            height
+        ));
+    }
+
+    out.clear();
+    out.resize(width * height * BYTES_PER_PIXEL, 0);
+    for y in 0..height {
+        let row = &frame[y * stride..y * stride + width * bytes_per_pixel];
+        let out_row = &mut out[y * width * BYTES_PER_PIXEL..(y + 1) * width * BYTES_PER_PIXEL];
+        for (src, dst) in row.chunks_exact(bytes_per_pixel).zip(out_row.chunks_exact_mut(BYTES_PER_PIXEL)) {
+            let (r, g, b) = match format {
+                PixelFormat::Bgra => (src[2], src[1], src[0]),
+                PixelFormat::Rgba => (src[0], src[1], src[2]),
+                PixelFormat::Xrgb => (src[1], src[2], src[3]),
+                PixelFormat::Rgb565 => {
+                    let value = u16::from_le_bytes([src[0], src[1]]);
+                    // Растягиваем 5 и 6 бит на весь диапазон 0–255
+                    let r = ((value >> 11) & 0x1F) as u32 * 255 / 31;
+                    let g = ((value >> 5) & 0x3F) as u32 * 255 / 63;
+                    let b = (value & 0x1F) as u32 * 255 / 31;
+                    (r as u8, g as u8, b as u8)
+                }
+            };
+            dst[CHANNEL_B] = b;
+            dst[CHANNEL_G] = g;
+            dst[CHANNEL_R] = r;
+            dst[CHANNEL_A] = 255;
+        }
+    }
+    Ok(())
+}
+
+/// Кадр захвата в BGRA и размер его строки в байтах: BGRA отдаётся как есть,
+/// остальные форматы переводятся в буфер `converted`.
+pub fn bgra_frame<'a>(
+    frame: &'a [u8],
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    converted: &'a mut Vec<u8>,
+) -> Result<(&'a [u8], usize), String> {
+    if format == PixelFormat::Bgra {
+        return Ok((frame, frame.len() / height.max(1)));
+    }
+    convert_to_bgra(frame, format, width, height, converted)?;
+    Ok((converted, width * BYTES_PER_PIXEL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Два пикселя: красноватый (200, 100, 50) и синий (0, 0, 255) в формате BGRA
+    const EXPECTED: [u8; 8] = [50, 100, 200, 255, 255, 0, 0, 255];
+
+    fn convert(frame: &[u8], format: PixelFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        convert_to_bgra(frame, format, 2, 1, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn converts_rgba() {
+        assert_eq!(convert(&[200, 100, 50, 0, 0, 0, 255, 0], PixelFormat::Rgba), EXPECTED);
+    }
+
+    #[test]
+    fn converts_xrgb() {
+        assert_eq!(convert(&[0, 200, 100, 50, 0, 0, 0, 255], PixelFormat::Xrgb), EXPECTED);
+    }
+
+    #[test]
+    fn converts_rgb565() {
+        let white = 0xFFFFu16.to_le_bytes();
+        let blue = 0x001Fu16.to_le_bytes();
+        let out = convert(&[white[0], white[1], blue[0], blue[1]], PixelFormat::Rgb565);
+        assert_eq!(out, [255, 255, 255, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn skips_row_padding() {
+        // Строка из двух пикселей RGBA и 4 байт выравнивания
+        let frame = [200, 100, 50, 0, 0, 0, 255, 0, 9, 9, 9, 9];
+        assert_eq!(convert(&frame, PixelFormat::Rgba), EXPECTED);
+    }
+
+    #[test]
+    fn bgra_frame_keeps_bgra_stride_and_converts_others() {
+        let mut converted = Vec::new();
+        // Строка BGRA с выравниванием остаётся как есть
+        let padded = [50, 100, 200, 255, 255, 0, 0, 255, 0, 0, 0, 0];
+        assert_eq!(bgra_frame(&padded, PixelFormat::Bgra, 2, 1, &mut converted), Ok((&padded[..], 12)));
+        let rgba = [200, 100, 50, 0, 0, 0, 255, 0];
+        assert_eq!(bgra_frame(&rgba, PixelFormat::Rgba, 2, 1, &mut converted), Ok((&EXPECTED[..], 8)));
+    }
+
+    #[test]
+    fn rejects_frame_too_small_for_format() {
+        let mut out = Vec::new();
+        assert!(convert_to_bgra(&[0; 4], PixelFormat::Rgba, 2, 1, &mut out).is_err());
+    }
+}
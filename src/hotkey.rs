@@ -0,0 +1,115 @@
+//! Глобальная горячая клавиша паузы (`pause_hotkey`). Регистрируется в системе, поэтому
+//! работает, даже когда окно программы не в фокусе. Поддерживается только на Windows.
+
+use crate::control::SharedState;
+use log::{error, warn};
+use std::thread;
+
+const MOD_ALT: u32 = 0x0001;
+const MOD_CONTROL: u32 = 0x0002;
+const MOD_SHIFT: u32 = 0x0004;
+const MOD_WIN: u32 = 0x0008;
+
+/// Сочетание клавиш: модификаторы `MOD_*` и виртуальный код клавиши Windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hotkey {
+    pub modifiers: u32,
+    pub key: u32,
+}
+
+/// Разбирает сочетание вроде `"Ctrl+Alt+L"` или `"Shift+F12"`: модификаторы Ctrl, Alt, Shift,
+/// Win и одна клавиша — буква, цифра или F1–F24. Регистр не важен.
+pub fn parse_hotkey(text: &str) -> Result<Hotkey, String> {
+    let error = || format!("pause_hotkey \"{}\": нужны модификаторы и одна клавиша, например \"Ctrl+Alt+L\"", text);
+    let mut modifiers = 0;
+    let mut key = None;
+    for part in text.split('+').map(str::trim) {
+        let part = part.to_ascii_uppercase();
+        let modifier = match part.as_str() {
+            "CTRL" | "CONTROL" => MOD_CONTROL,
+            "ALT" => MOD_ALT,
+            "SHIFT" => MOD_SHIFT,
+            "WIN" => MOD_WIN,
+            _ => 0,
+        };
+        if modifier != 0 {
+            modifiers |= modifier;
+            continue;
+        }
+        if key.is_some() {
+            return Err(error());
+        }
+        // Коды букв и цифр совпадают с ASCII, F1–F24 идут подряд с 0x70
+        key = match part.as_bytes() {
+            [c] if c.is_ascii_alphanumeric() => Some(*c as u32),
+            [b'F', number @ ..] => match std::str::from_utf8(number).ok().and_then(|n| n.parse::<u32>().ok()) {
+                Some(n @ 1..=24) => Some(0x70 + n - 1),
+                _ => return Err(error()),
+            },
+            _ => return Err(error()),
+        };
+    }
+    match key {
+        Some(key) => Ok(Hotkey { modifiers, key }),
+        None => Err(error()),
+    }
+}
+
+/// Ставит или снимает паузу.
+#[cfg(windows)]
+fn toggle_pause(state: &SharedState) {
+    let mut state = state.lock().unwrap();
+    state.paused = !state.paused;
+    log::info!("{}", if state.paused { "Пауза: лента погашена" } else { "Пауза снята" });
+}
+
+/// Регистрирует клавишу и ждёт её нажатий в очереди сообщений своего потока.
+#[cfg(windows)]
+fn listen(hotkey: Hotkey, state: SharedState) {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{MOD_NOREPEAT, RegisterHotKey};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY};
+
+    // SAFETY: окно не передаётся, поэтому WM_HOTKEY приходит в очередь этого потока
+    let registered = unsafe { RegisterHotKey(std::ptr::null_mut(), 1, hotkey.modifiers | MOD_NOREPEAT, hotkey.key) };
+    if registered == 0 {
+        error!("Не удалось зарегистрировать pause_hotkey: сочетание, вероятно, занято другой программой");
+        return;
+    }
+    // SAFETY: MSG — простая структура, для которой нули — допустимое значение
+    let mut message: MSG = unsafe { std::mem::zeroed() };
+    // SAFETY: GetMessageW пишет только в переданную структуру MSG
+    while unsafe { GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) } > 0 {
+        if message.message == WM_HOTKEY {
+            toggle_pause(&state);
+        }
+    }
+}
+
+/// Вне Windows глобальные клавиши не регистрируются; пауза доступна командой `set_paused`.
+#[cfg(not(windows))]
+fn listen(_hotkey: Hotkey, _state: SharedState) {
+    warn!("pause_hotkey поддерживается только на Windows; используйте команду set_paused");
+}
+
+/// Запускает поток, который по нажатию `hotkey` ставит и снимает паузу.
+pub fn spawn_listener(hotkey: Hotkey, state: SharedState) {
+    if let Err(e) = thread::Builder::new().name("hotkey".to_string()).spawn(move || listen(hotkey, state)) {
+        error!("Не удалось запустить поток горячей клавиши: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkey_is_parsed_from_text() {
+        assert_eq!(parse_hotkey("Ctrl+Alt+L"), Ok(Hotkey { modifiers: MOD_CONTROL | MOD_ALT, key: b'L' as u32 }));
+        assert_eq!(parse_hotkey("shift + f12"), Ok(Hotkey { modifiers: MOD_SHIFT, key: 0x7B }));
+        assert_eq!(parse_hotkey("Win+1"), Ok(Hotkey { modifiers: MOD_WIN, key: b'1' as u32 }));
+        assert!(parse_hotkey("Ctrl+Alt").is_err());
+        assert!(parse_hotkey("Ctrl+A+B").is_err());
+        assert!(parse_hotkey("Ctrl+F25").is_err());
+        assert!(parse_hotkey("Ctrl+Space").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::AmbilightConfig;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Наибольшее тело запроса, которое принимает сервер.
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// То, что отдаёт `GET /status`.
+#[derive(Debug, Default, Serialize)]
+pub struct Status {
+    /// Кадров в секунду, ушедших на ленту за последнюю секунду
+    pub fps: usize,
+    pub led_count: usize,
+    /// Последние выведенные цвета
+    pub colors: Vec<(u8, u8, u8)>,
+}
+
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+/// Тело `POST /config`: меняются только переданные поля.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigUpdate {
+    /// Яркость в процентах
+    brightness: Option<usize>,
+    gamma: Option<f32>,
+    saturation: Option<f32>,
+}
+
+/// То, с чем работает HTTP-сервер.
+#[derive(Clone)]
+pub struct HttpContext {
+    pub status: SharedStatus,
+    /// Текущие настройки; изменённые сервером подхватывает главный цикл
+    pub config: Arc<Mutex<AmbilightConfig>>,
+    /// Выставляется, когда настройки изменены через `POST /config`
+    pub config_updated: Arc<AtomicBool>,
+}
+
+/// Применяет изменения к копии настроек и проверяет результат.
+fn apply_update(config: &AmbilightConfig, update: ConfigUpdate) -> Result<AmbilightConfig, String> {
+    let mut config = config.clone();
+    if let Some(brightness) = update.brightness {
+        config.brightness = brightness;
+    }
+    if let Some(gamma) = update.gamma {
+        config.gamma = gamma;
+    }
+    if let Some(saturation) = update.saturation {
+        config.saturation = saturation;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// Разбирает запрос и возвращает код ответа и JSON-тело.
+/// `POST /config` принимает только JSON: такой запрос браузер не отправит с чужой страницы
+/// без предварительного запроса CORS, а на него сервер не разрешает доступ.
+fn handle_request(
+    method: &str,
+    path: &str,
+    content_type: &str,
+    body: &[u8],
+    context: &HttpContext,
+) -> (&'static str, serde_json::Value) {
+    match (method, path) {
+        ("GET", "/status") => ("200 OK", serde_json::json!(*context.status.lock().unwrap())),
+        ("POST", "/config") if !is_json(content_type) => (
+            "415 Unsupported Media Type",
+            serde_json::json!({ "ok": false, "error": "нужен Content-Type: application/json" }),
+        ),
+        ("POST", "/config") => {
+            let update: ConfigUpdate = match serde_json::from_slice(body) {
+                Ok(update) => update,
+                Err(e) => return ("400 Bad Request", serde_json::json!({ "ok": false, "error": e.to_string() })),
+            };
+            let mut config = context.config.lock().unwrap();
+            match apply_update(&config, update) {
+                Ok(updated) => {
+                    *config = updated;
+                    context.config_updated.store(true, Ordering::Relaxed);
+                    ("200 OK", serde_json::json!({ "ok": true }))
+                }
+                Err(e) => ("400 Bad Request", serde_json::json!({ "ok": false, "error": e })),
+            }
+        }
+        _ => ("404 Not Found", serde_json::json!({ "ok": false, "error": "неизвестный путь" })),
+    }
+}
+
+/// Это `application/json`, возможно с параметрами вроде `; charset=utf-8`.
+fn is_json(content_type: &str) -> bool {
+    content_type.split(';').next().is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn handle_client(mut stream: TcpStream, context: HttpContext) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let mut content_length = 0;
+    let mut content_type = String::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = value.trim().to_string();
+            }
+        }
+        line.clear();
+    }
+    let mut body = vec![0; content_length.min(MAX_BODY_LEN)];
+    reader.read_exact(&mut body)?;
+
+    let (code, response) = handle_request(method, path, &content_type, &body, &context);
+    let response = response.to_string();
+    // Чужим страницам разрешено только читать состояние
+    let cors = if method == "GET" { "Access-Control-Allow-Origin: *\r\n" } else { "" };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        code,
+        response.len(),
+        cors,
+        response
+    )
+}
+
+/// Запускает HTTP-сервер состояния на `bind:port`: `GET /status` и `POST /config`.
+pub fn spawn_server(bind: &str, port: u16, context: HttpContext) -> io::Result<()> {
+    let listener = TcpListener::bind((bind, port))?;
+    info!("HTTP-сервер состояния слушает {}:{}", bind, port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let context = context.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, context) {
+                            warn!("Ошибка HTTP-клиента: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Ошибка подключения к HTTP-серверу: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CONFIG;
+
+    #[test]
+    fn config_update_changes_only_given_fields() {
+        let config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        let update = serde_json::from_str(r#"{"brightness": 40, "saturation": 1.3}"#).unwrap();
+        let updated = apply_update(&config, update).unwrap();
+        assert_eq!(updated.brightness, 40);
+        assert_eq!(updated.saturation, 1.3);
+        assert_eq!(updated.gamma, config.gamma);
+
+        let update = serde_json::from_str(r#"{"gamma": -1.0}"#).unwrap();
+        assert!(apply_update(&config, update).is_err());
+        assert!(serde_json::from_str::<ConfigUpdate>(r#"{"fps": 30}"#).is_err());
+    }
+
+    #[test]
+    fn config_is_changed_only_by_json_requests() {
+        let config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        let context = HttpContext {
+            status: SharedStatus::default(),
+            config: Arc::new(Mutex::new(config)),
+            config_updated: Arc::new(AtomicBool::new(false)),
+        };
+        let body = br#"{"brightness": 40}"#;
+        let (code, _) = handle_request("POST", "/config", "text/plain", body, &context);
+        assert_eq!(code, "415 Unsupported Media Type");
+        assert!(!context.config_updated.load(Ordering::Relaxed));
+
+        let (code, _) = handle_request("POST", "/config", "application/json; charset=utf-8", body, &context);
+        assert_eq!(code, "200 OK");
+        assert_eq!(context.config.lock().unwrap().brightness, 40);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::color::hsv_to_rgb;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+/// Период смены оттенка в `fade`, секунды.
+const FADE_PERIOD_SECS: f32 = 60.0;
+/// За сколько секунд радуга проходит по ленте полный круг.
+const RAINBOW_PERIOD_SECS: f32 = 20.0;
+/// Период одного «вдоха» в `breathing`, секунды.
+const BREATHING_PERIOD_SECS: f32 = 6.0;
+
+/// Настройки затухания при неподвижной картинке.
+#[derive(Debug, Clone, Copy)]
+pub struct IdleSettings {
+    /// Сколько картинка должна стоять, прежде чем лента начнёт гаснуть
+    pub timeout: Duration,
+    /// Изменение канала, которое не считается движением
+    pub threshold: u8,
+    /// Время затухания до нуля
+    pub fade_out: Duration,
+    /// Время возврата к полной яркости (0 — сразу)
+    pub fade_in: Duration,
+}
+
+/// Что показывает лента вместо погасшей картинки.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleAnimation {
+    /// Лента гаснет
+    #[default]
+    Off,
+    /// Вся лента медленно меняет оттенок
+    Fade,
+    /// Радуга, бегущая по ленте
+    Rainbow,
+    /// `static_color`, плавно разгорающийся и гаснущий
+    Breathing,
+}
+
+/// Кадр анимации на `led_count` светодиодов в момент `time` с начала работы.
+/// Для `Off` — чёрный.
+pub fn animation_frame(
+    animation: IdleAnimation,
+    led_count: usize,
+    time: Duration,
+    static_color: (u8, u8, u8),
+) -> Vec<(u8, u8, u8)> {
+    let time = time.as_secs_f32();
+    match animation {
+        IdleAnimation::Off => vec![(0, 0, 0); led_count],
+        IdleAnimation::Fade => vec![hsv_to_rgb((360.0 * time / FADE_PERIOD_SECS, 1.0, 1.0)); led_count],
+        IdleAnimation::Rainbow => (0..led_count)
+            .map(|i| {
+                let offset = i as f32 / led_count as f32 + time / RAINBOW_PERIOD_SECS;
+                hsv_to_rgb((360.0 * offset, 1.0, 1.0))
+            })
+            .collect(),
+        IdleAnimation::Breathing => {
+            // Косинус от 0 до 1: в начале каждого периода лента тёмная
+            let level = (1.0 - (TAU * time / BREATHING_PERIOD_SECS).cos()) / 2.0;
+            let (r, g, b) = static_color;
+            let scale = |c: u8| (c as f32 * level) as u8;
+            vec![(scale(r), scale(g), scale(b)); led_count]
+        }
+    }
+}
+
+/// Гасит ленту, когда изображение долго не меняется (статичный рабочий стол, заставка).
+#[derive(Default)]
+pub struct IdleDimmer {
+    /// Цвета, с которыми сравниваются новые кадры
+    reference: Vec<(u8, u8, u8)>,
+    idle_for: Duration,
+    /// Текущий множитель яркости 0.0–1.0
+    factor: f32,
+}
+
+impl IdleDimmer {
+    /// Учитывает новый кадр (прошло `elapsed` с предыдущего) и возвращает множитель яркости.
+    pub fn update(&mut self, colors: &[(u8, u8, u8)], elapsed: Duration, settings: &IdleSettings) -> f32 {
+        let changed = self.reference.len() != colors.len()
+            || colors.iter().zip(&self.reference).any(|(new, old)| {
+                new.0.abs_diff(old.0) > settings.threshold
+                    || new.1.abs_diff(old.1) > settings.threshold
+                    || new.2.abs_diff(old.2) > settings.threshold
+            });
+
+        if changed {
+            self.reference.clear();
+            self.reference.extend_from_slice(colors);
+            self.idle_for = Duration::ZERO;
+        } else {
+            self.idle_for += elapsed;
+        }
+
+        let step = |fade: Duration| if fade.is_zero() { 1.0 } else { elapsed.as_secs_f32() / fade.as_secs_f32() };
+        self.factor = if self.idle_for >= settings.timeout {
+            (self.factor - step(settings.fade_out)).max(0.0)
+        } else {
+            (self.factor + step(settings.fade_in)).min(1.0)
+        };
+        self.factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: IdleSettings = IdleSettings {
+        timeout: Duration::from_secs(10),
+        threshold: 2,
+        fade_out: Duration::from_secs(2),
+        fade_in: Duration::ZERO,
+    };
+
+    #[test]
+    fn fades_out_on_static_frames_and_restores_on_change() {
+        let mut dimmer = IdleDimmer::default();
+        let second = Duration::from_secs(1);
+        assert_eq!(dimmer.update(&[(100, 100, 100)], second, &SETTINGS), 1.0);
+
+        // Шум в пределах порога не считается движением
+        for _ in 0..10 {
+            dimmer.update(&[(101, 99, 100)], second, &SETTINGS);
+        }
+        assert_eq!(dimmer.update(&[(100, 100, 100)], second, &SETTINGS), 0.0);
+
+        assert_eq!(dimmer.update(&[(150, 100, 100)], second, &SETTINGS), 1.0);
+    }
+
+    #[test]
+    fn animations_fill_the_strip() {
+        let second = Duration::from_secs(1);
+        assert_eq!(animation_frame(IdleAnimation::Off, 3, second, (255, 255, 255)), vec![(0, 0, 0); 3]);
+        assert_eq!(animation_frame(IdleAnimation::Fade, 2, Duration::ZERO, (0, 0, 0)), vec![(255, 0, 0); 2]);
+
+        let rainbow = animation_frame(IdleAnimation::Rainbow, 3, Duration::ZERO, (0, 0, 0));
+        assert_eq!(rainbow, [(255, 0, 0), (0, 255, 0), (0, 0, 255)]);
+
+        let breathing =
+            |secs: f32| animation_frame(IdleAnimation::Breathing, 1, Duration::from_secs_f32(secs), (200, 100, 0));
+        assert_eq!(breathing(0.0), [(0, 0, 0)]);
+        assert_eq!(breathing(BREATHING_PERIOD_SECS / 2.0), [(200, 100, 0)]);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::regions::LedRegion;
+use log::info;
+use serde::Deserialize;
+use std::error::Error;
+
+/// Регион светодиода в долях экрана (0.0–1.0).
+pub struct NormalizedRegion {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+#[derive(Deserialize)]
+struct Scan {
+    minimum: f32,
+    maximum: f32,
+}
+
+/// Светодиод в формате Hyperion.ng (`hmin`/`hmax`/`vmin`/`vmax`)
+/// или классического Hyperion (`hscan`/`vscan`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HyperionLed {
+    Ng { hmin: f32, hmax: f32, vmin: f32, vmax: f32 },
+    Classic { hscan: Scan, vscan: Scan },
+}
+
+/// Файл может содержать сам массив `ledConfig` или объект конфигурации с полем `leds`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HyperionLayout {
+    Leds(Vec<HyperionLed>),
+    Config { leds: Vec<HyperionLed> },
+}
+
+/// Читает раскладку светодиодов из JSON-конфигурации Hyperion и проверяет её.
+pub fn load_hyperion_layout(path: &str) -> Result<Vec<NormalizedRegion>, Box<dyn Error>> {
+    let data = std::fs::read_to_string(path)?;
+    let leds = match serde_json::from_str(&data)? {
+        HyperionLayout::Leds(leds) | HyperionLayout::Config { leds } => leds,
+    };
+    if leds.is_empty() {
+        return Err(format!("В {} нет ни одного светодиода", path).into());
+    }
+
+    let regions: Vec<NormalizedRegion> = leds
+        .into_iter()
+        .map(|led| match led {
+            HyperionLed::Ng { hmin, hmax, vmin, vmax } => {
+                NormalizedRegion { x1: hmin, y1: vmin, x2: hmax, y2: vmax }
+            }
+            HyperionLed::Classic { hscan, vscan } => NormalizedRegion {
+                x1: hscan.minimum,
+                y1: vscan.minimum,
+                x2: hscan.maximum,
+                y2: vscan.maximum,
+            },
+        })
+        .collect();
+
+    for (index, region) in regions.iter().enumerate() {
+        let valid = |min: f32, max: f32| (0.0..=1.0).contains(&min) && (0.0..=1.0).contains(&max) && min < max;
+        if !valid(region.x1, region.x2) || !valid(region.y1, region.y2) {
+            return Err(format!("Светодиод {} в {}: границы должны быть в 0.0–1.0 и min < max", index, path).into());
+        }
+    }
+
+    info!("Импортировано светодиодов из {}: {}", path, regions.len());
+    Ok(regions)
+}
+
+/// Переводит регионы в долях экрана в пиксели; каждый регион содержит хотя бы один пиксель.
+pub fn to_pixel_regions(regions: &[NormalizedRegion], width: usize, height: usize) -> Vec<LedRegion> {
+    let scale = |min: f32, max: f32, size: usize| {
+        let start = ((min * size as f32).floor() as usize).min(size.saturating_sub(1));
+        let end = ((max * size as f32).ceil() as usize).clamp(start + 1, size.max(1));
+        (start, end)
+    };
+    regions
+        .iter()
+        .map(|region| {
+            let (x1, x2) = scale(region.x1, region.x2, width);
+            let (y1, y2) = scale(region.y1, region.y2, height);
+            LedRegion { x1, y1, x2, y2, edge: None }
+        })
+        .collect()
+}
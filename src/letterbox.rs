@@ -0,0 +1,103 @@
+use crate::color::{BYTES_PER_PIXEL, CHANNEL_B, CHANNEL_G, CHANNEL_R};
+use crate::regions::CaptureArea;
+
+/// Яркость канала, до которой пиксель считается чёрной полосой.
+const DARK_THRESHOLD: u8 = 24;
+/// Шаг проверки пикселей вдоль строки.
+const SAMPLE_STEP: usize = 8;
+
+/// Строка целиком тёмная (проверяется каждый `SAMPLE_STEP`-й пиксель).
+fn row_is_dark(frame: &[u8], stride: usize, y: usize, area: CaptureArea) -> bool {
+    let row = &frame[y * stride + area.x * BYTES_PER_PIXEL..y * stride + (area.x + area.width) * BYTES_PER_PIXEL];
+    row.chunks_exact(BYTES_PER_PIXEL).step_by(SAMPLE_STEP).all(|pixel| {
+        pixel[CHANNEL_R] <= DARK_THRESHOLD && pixel[CHANNEL_G] <= DARK_THRESHOLD && pixel[CHANNEL_B] <= DARK_THRESHOLD
+    })
+}
+
+/// Высота чёрных полос сверху и снизу `area` (берётся меньшая из двух).
+/// Возвращает `None`, если изображения не нашлось вовсе (например, кадр полностью чёрный):
+/// по такому кадру о полосах ничего сказать нельзя.
+pub fn detect_bar_height(frame: &[u8], stride: usize, area: CaptureArea) -> Option<usize> {
+    // Полосы не бывают выше трети экрана
+    let limit = area.height / 3;
+    let top = (0..limit).take_while(|&i| row_is_dark(frame, stride, area.y + i, area)).count();
+    let bottom = (0..limit)
+        .take_while(|&i| row_is_dark(frame, stride, area.y + area.height - 1 - i, area))
+        .count();
+    if top == limit && bottom == limit {
+        return None;
+    }
+    Some(top.min(bottom))
+}
+
+/// Область `area` без полос высотой `bar` сверху и снизу.
+pub fn inner_area(area: CaptureArea, bar: usize) -> CaptureArea {
+    CaptureArea { y: area.y + bar, height: area.height - 2 * bar, ..area }
+}
+
+/// Периодически пересчитывает высоту полос, чтобы регионы не дёргались каждый кадр.
+pub struct LetterboxDetector {
+    interval: u32,
+    frames: u32,
+    bar: usize,
+}
+
+impl LetterboxDetector {
+    pub fn new(interval: u32) -> Self {
+        LetterboxDetector { interval: interval.max(1), frames: 0, bar: 0 }
+    }
+
+    /// Текущая высота полос.
+    pub fn bar(&self) -> usize {
+        self.bar
+    }
+
+    /// Сбрасывает найденные полосы (после смены разрешения).
+    pub fn reset(&mut self) {
+        self.bar = 0;
+        self.frames = 0;
+    }
+
+    /// Раз в `interval` кадров ищет полосы; возвращает новую высоту, если она изменилась.
+    pub fn update(&mut self, frame: &[u8], stride: usize, area: CaptureArea) -> Option<usize> {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return None;
+        }
+        self.frames = 0;
+
+        let bar = detect_bar_height(frame, stride, area)?;
+        if bar == self.bar {
+            return None;
+        }
+        self.bar = bar;
+        Some(bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Кадр 16x12, у которого строки `from..to` светлые, остальные чёрные.
+    fn frame(from: usize, to: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; 16 * 12 * BYTES_PER_PIXEL];
+        for y in from..to {
+            frame[y * 16 * BYTES_PER_PIXEL..(y + 1) * 16 * BYTES_PER_PIXEL].fill(200);
+        }
+        frame
+    }
+
+    #[test]
+    fn finds_bars_and_falls_back_to_full_screen() {
+        let area = CaptureArea { x: 0, y: 0, width: 16, height: 12 };
+        let stride = 16 * BYTES_PER_PIXEL;
+
+        assert_eq!(detect_bar_height(&frame(3, 9), stride, area), Some(3));
+        // Полоса только сверху — это тёмная сцена, а не letterbox
+        assert_eq!(detect_bar_height(&frame(3, 12), stride, area), Some(0));
+        assert_eq!(detect_bar_height(&frame(0, 12), stride, area), Some(0));
+        // Полностью чёрный кадр ничего не говорит о полосах
+        assert_eq!(detect_bar_height(&frame(0, 0), stride, area), None);
+    }
+}
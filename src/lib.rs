@@ -0,0 +1,45 @@
+//! Движок Ambilight: расчёт цветов ленты по кадру экрана и вывод на контроллер.
+//! Бинарник `main.rs` добавляет к нему захват экрана, командную строку и службу;
+//! для встраивания в другие программы есть [`Ambilight`].
+
+pub mod bench;
+pub mod calibration;
+pub mod color;
+pub mod config;
+pub mod contrast;
+pub mod control;
+pub mod ddp;
+pub mod displays;
+pub mod dmx;
+pub mod engine;
+pub mod focus;
+pub mod format;
+pub mod hotkey;
+pub mod http;
+pub mod idle;
+pub mod import;
+pub mod letterbox;
+pub mod logging;
+pub mod mirror;
+pub mod nanoleaf;
+pub mod output;
+pub mod overlay;
+pub mod pacing;
+pub mod packet;
+pub mod power;
+pub mod profile;
+pub mod rate_limit;
+pub mod regions;
+pub mod remap;
+pub mod schedule;
+pub mod segments;
+pub mod selftest;
+pub mod shutdown;
+pub mod smoothing;
+pub mod status;
+pub mod stream;
+pub mod strips;
+pub mod svg;
+pub mod writer;
+
+pub use engine::Ambilight;
@@ -0,0 +1,99 @@
+//! Журнал на `log` и `env_logger`. Уровень задаётся переменной окружения `RUST_LOG`
+//! в формате env_logger (например, `warn` или `ambilight=debug,info`), по умолчанию — `info`.
+//!
+//! Сообщения пишутся в stderr, а после [`log_to_file`] — в файл с ротацией.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// Размер файла журнала, после которого он переименовывается в `<файл>.1` и начинается заново.
+const LOG_FILE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Файл журнала с ротацией: хранятся текущий файл и один предыдущий.
+struct LogFile {
+    path: String,
+    /// `None` во время ротации: на Windows открытый файл не переименовать
+    file: Option<File>,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    fn open(path: &str, max_size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path: path.to_string(), file: Some(file), size, max_size })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.file = None;
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+            *self = LogFile::open(&self.path, self.max_size)?;
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            // Прошлая ротация не удалась: пробуем открыть файл заново
+            None => self.file.insert(OpenOptions::new().create(true).append(true).open(&self.path)?),
+        };
+        file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Куда env_logger пишет готовые строки: в файл журнала, если он задан, иначе в stderr.
+struct LogSink;
+
+impl Write for LogSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match LOG_FILE.lock().unwrap().as_mut() {
+            Some(file) => file.write_line(buf)?,
+            None => io::stderr().write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Устанавливает журнал с уровнем из `RUST_LOG`. Вызывается один раз при запуске.
+pub fn init() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .target(env_logger::Target::Pipe(Box::new(LogSink)))
+        .format(|buf, record| {
+            let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+            writeln!(buf, "[{} {:<5}] {}", time, record.level(), record.args())
+        })
+        .init();
+}
+
+/// Дальше журнал пишется в файл `path` (дописывается), а не в stderr.
+pub fn log_to_file(path: &str) -> io::Result<()> {
+    *LOG_FILE.lock().unwrap() = Some(LogFile::open(path, LOG_FILE_MAX_BYTES)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_file_rotates_when_full() {
+        let path = std::env::temp_dir().join(format!("ambilight-log-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let mut log = LogFile::open(path, 10).unwrap();
+        log.write_line(b"first\n").unwrap();
+        log.write_line(b"second\n").unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "second\n");
+        assert_eq!(fs::read_to_string(format!("{}.1", path)).unwrap(), "first\n");
+        fs::remove_file(path).unwrap();
+        fs::remove_file(format!("{}.1", path)).unwrap();
+    }
+}
@@ -1,316 +1,538 @@
+mod cli;
+mod daemon;
+
+use ambilight::{
+    Ambilight, bench, calibration, color, config, control, displays, engine, focus, format, hotkey, http, logging,
+    nanoleaf, output, overlay, pacing, regions, remap, segments, selftest, shutdown, strips, stream, svg,
+};
+use engine::{build_led_regions, load_imported_layout};
+use cli::{Action, Cli};
+use config::{AmbilightConfig, ConfigErrorPolicy};
+use displays::ExtraDisplays;
+use control::{ControlContext, Mode, RuntimeState};
+use focus::FocusTracker;
+use format::PixelFormat;
+use http::{HttpContext, Status};
+use nanoleaf::Nanoleaf;
+use output::OutputMode;
+use pacing::{CaptureRateMonitor, FramePacer};
+use regions::{CaptureArea, create_led_regions};
+use log::{debug, error, info, warn};
 use scrap::{Capturer, Display};
-use serialport;
-use serde::Deserialize;
-use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use rayon::prelude::*;
-
-#[derive(Debug, Deserialize)]
-struct AmbilightConfig {
-    fps: u32,
-    port_name: String,
-    baud_rate: u32,
-
-    top_led_count: usize,
-    left_led_count: usize,
-    right_led_count: usize,
-    bottom_left_led_count: usize,
-    bottom_right_led_count: usize,
-    offset: usize,
-
-    invert_direction: bool,
-    pixel_thickness: usize,
-
-    brightness: usize,
-    white_balance_temperature: f32,
-    gamma: f32,
+use strips::Outputs;
+
+/// Как часто проверять, не изменился ли файл настроек.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Самая длинная пауза между опросами захвата, когда экран обновляется редко.
+const CAPTURE_BACKOFF_MAX: Duration = Duration::from_millis(16);
+/// Сколько ждать нового кадра, прежде чем пересоздать захват (RDP, смена дисплея).
+const CAPTURE_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Как часто на паузе проверять, не снята ли она.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Подходит ли кадр длиной `len` байт к разрешению `width` x `height`: строки BGRA
+/// могут быть выровнены, но не короче `width` пикселей.
+fn frame_fits(len: usize, width: usize, height: usize, pixel_format: PixelFormat) -> bool {
+    if pixel_format != PixelFormat::Bgra {
+        return len >= width * height * pixel_format.bytes_per_pixel();
+    }
+    height > 0 && len.is_multiple_of(height) && len / height >= width * color::BYTES_PER_PIXEL
 }
 
-// Определяем область экрана для одного светодиода
-struct LedRegion {
-    x1: usize,
-    y1: usize,
-    x2: usize,
-    y2: usize,
-}
+const NO_LEDS_ERROR: &str = "В настройках нет ни одного светодиода: задайте *_led_count (или matrix_columns/matrix_rows)";
 
-/// Вычисляет множители для коррекции белого по заданной цветовой температуре (в Кельвинах).
-fn color_temperature_to_rgb_multipliers(temp: f32) -> (f32, f32, f32) {
-    let temp = temp / 100.0;
-    let (r, g, b): (f32, f32, f32);
-    if temp <= 66.0 {
-        r = 255.0;
-        g = 99.4708025861 * (temp.max(1.0)).ln() - 161.1195681661;
-        b = if temp <= 19.0 {
-            0.0
-        } else {
-            138.5177312231 * ((temp - 10.0).max(1.0)).ln() - 305.0447927307
-        };
-    } else {
-        r = 329.698727446 * ((temp - 60.0).max(1.0)).powf(-0.1332047592);
-        g = 288.1221695283 * ((temp - 60.0).max(1.0)).powf(-0.0755148492);
-        b = 255.0;
+/// Число светодиодов ленты без захвата экрана. Регионы строятся для условного
+/// разрешения: их число от разрешения не зависит.
+fn strip_led_count(config: &AmbilightConfig) -> Result<usize, Box<dyn std::error::Error>> {
+    let (width, height) = (1920, 1080);
+    let imported = load_imported_layout(config)?;
+    let mut count = build_led_regions(config, &imported, CaptureArea { x: 0, y: 0, width, height }).len();
+    for display in config.displays.iter().skip(1) {
+        count += create_led_regions(&display.apply_to(config), width, height).len();
     }
-    (r / 255.0, g / 255.0, b / 255.0)
+    Ok(count)
 }
 
-/// Создаёт вектор регионов (LedRegion) в нужном порядке.
-fn create_led_regions(config: &AmbilightConfig, width: usize, height: usize) -> Vec<LedRegion> {
-    let mut regions = Vec::new();
-
-    let pixel_thickness = height * config.pixel_thickness / 100;
-
-    let total_bottom = config.bottom_left_led_count + config.bottom_right_led_count;
-    if total_bottom == 0 {
-        return regions; // Если снизу нет диодов, вернём пустой вектор
+/// Открывает захват основного дисплея.
+fn open_capturer(config: &AmbilightConfig) -> std::io::Result<Capturer> {
+    match config.displays.first() {
+        Some(display) => displays::open_display(display.index),
+        None => Capturer::new(Display::primary()?),
     }
+}
 
-    let offset_pixels = width * config.offset / 100;
-    let effective_width = width.saturating_sub(offset_pixels);
-    let left_ratio = config.bottom_left_led_count as f32 / total_bottom as f32;
-    let left_group_width = (left_ratio * effective_width as f32).round() as usize;
-    let right_ratio = config.bottom_right_led_count as f32 / total_bottom as f32;
-    let right_group_width = (right_ratio * effective_width as f32).round() as usize;
-    let right_group_start = left_group_width + offset_pixels;
-
-    // 1) Нижняя правая группа: слева → направо
-    if config.bottom_right_led_count > 0 {
-        let seg_w = right_group_width as f32 / config.bottom_right_led_count as f32;
-        for i in 0..config.bottom_right_led_count {
-            let x1 = (right_group_start as f32 + i as f32 * seg_w).round() as usize;
-            let x2 = (right_group_start as f32 + (i + 1) as f32 * seg_w).round() as usize;
-            regions.push(LedRegion {
-                x1: x1.min(width),
-                y1: height.saturating_sub(pixel_thickness),
-                x2: x2.min(width),
-                y2: height,
-            });
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    logging::init();
+    let mut cli = cli::parse()?;
+
+    // Режимы калибровки не требуют ни настроек, ни порта
+    match &cli.action {
+        Action::CalibrationPattern(path) => return calibration::write_pattern(path),
+        Action::Calibrate(path) => return calibration::run(path),
+        Action::SelfTest => {
+            shutdown::install_handler();
+            let config = config::load_config(&cli.config_path, cli.on_config_error)?;
+            let led_count = strip_led_count(&config)?;
+            if led_count == 0 {
+                return Err(NO_LEDS_ERROR.into());
+            }
+            remap::validate_remap(&config.led_remap, led_count)?;
+            strips::validate_strips(&config.strips, led_count)?;
+            config.output.validate_universes(led_count)?;
+            return selftest::run(&config, led_count);
         }
+        _ => {}
     }
 
-    // 2) Правая сторона: снизу → вверх
-    if config.right_led_count > 0 {
-        let seg_h = height as f32 / config.right_led_count as f32;
-        for i in 0..config.right_led_count {
-            let y1 = (height as f32 - (i + 1) as f32 * seg_h).round() as usize;
-            let y2 = (height as f32 - i as f32 * seg_h).round() as usize;
-            regions.push(LedRegion {
-                x1: width.saturating_sub(pixel_thickness),
-                y1: y1.min(height),
-                x2: width,
-                y2: y2.min(height),
-            });
+    // Автозапуск без консоли: --log-file тут задаёт только журнал, в службу программа не уходит
+    if cli.background {
+        daemon::run_in_background(&cli.log_file)?;
+        // PID-файл в этом режиме не пишется, поэтому и удалять его при выходе не нужно
+        cli.daemon = None;
+        shutdown::install_handler();
+        // Консоли уже нет, поэтому ошибка остаётся только в журнале
+        return run(cli).inspect_err(|e| error!("{}", e));
+    }
+    if let Some(options) = &cli.daemon {
+        #[cfg(unix)]
+        daemon::daemonize(options)?;
+        #[cfg(windows)]
+        {
+            let options = options.clone();
+            return Ok(daemon::run_service(&options, move || {
+                if let Err(e) = run(cli) {
+                    error!("{}", e);
+                }
+            })?);
         }
     }
+    shutdown::install_handler();
+    run(cli)
+}
 
-    // 3) Верхняя сторона: справа → налево
-    if config.top_led_count > 0 {
-        let seg_w = width as f32 / config.top_led_count as f32;
-        for i in 0..config.top_led_count {
-            let rev_i = config.top_led_count - 1 - i;
-            let x1 = (rev_i as f32 * seg_w).round() as usize;
-            let x2 = ((rev_i + 1) as f32 * seg_w).round() as usize;
-            regions.push(LedRegion {
-                x1: x1.min(width),
-                y1: 0,
-                x2: x2.min(width),
-                y2: pixel_thickness,
-            });
-        }
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    // 1. Чтение настроек
+    let config_path = cli.config_path.as_str();
+    let mut config = config::load_config(config_path, cli.on_config_error)?;
+    let mut config_modified = config::modified_time(config_path);
+    debug!("Настройки: {:#?}", config);
+
+    // 2. Настройка захвата экрана
+    let mut capturer = open_capturer(&config)?;
+    let (mut width, mut height) = (capturer.width(), capturer.height());
+    info!("Экран: {}x{}, формат пикселей: {:?}", width, height, config.pixel_format);
+
+    // 3. Генерация регионов и вся обработка цветов: захват и вывод остаются здесь
+    let mut engine = Ambilight::new(config.clone(), width, height)?;
+    let mut focus_interval = Duration::from_millis(config.focus_poll_interval_ms);
+    let mut focus_tracker = config
+        .follow_window_focus
+        .then(|| FocusTracker::new(focus_interval, engine.capture_area()));
+
+    // Остальные дисплеи: их цвета идут на ленте следом за основным
+    let mut extra_displays = ExtraDisplays::open(&config)?;
+
+    // Без светодиодов поток пустых пакетов только занимает порт
+    let led_count = engine.regions().len() + extra_displays.led_count();
+    if led_count == 0 {
+        return Err(NO_LEDS_ERROR.into());
+    }
+    segments::validate_segments(&config.segments, led_count)?;
+    remap::validate_remap(&config.led_remap, led_count)?;
+    strips::validate_strips(&config.strips, led_count)?;
+    config.output.validate_universes(led_count)?;
+
+    // Экспорт схемы раскладки не требует порта: сохраняем и выходим
+    if let Action::ExportSvg(path) = &cli.action {
+        svg::write_layout_svg(path, engine.regions(), width, height)?;
+        info!("Схема раскладки сохранена в {}", path);
+        return Ok(());
     }
 
-    // 4) Левая сторона: сверху → вниз
-    if config.left_led_count > 0 {
-        let seg_h = height as f32 / config.left_led_count as f32;
-        for i in 0..config.left_led_count {
-            let y1 = (i as f32 * seg_h).round() as usize;
-            let y2 = ((i + 1) as f32 * seg_h).round() as usize;
-            regions.push(LedRegion {
-                x1: 0,
-                y1: y1.min(height),
-                x2: pixel_thickness,
-                y2: y2.min(height),
-            });
-        }
+    // Снимок экрана с регионами тоже не требует порта
+    if let Action::DumpRegions(path) = &cli.action {
+        let frame = loop {
+            match capturer.frame() {
+                Ok(frame) => break frame,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(e.into()),
+            }
+        };
+        let mut converted_frame = Vec::new();
+        let (frame, stride) = format::bgra_frame(&frame, config.pixel_format, width, height, &mut converted_frame)?;
+        overlay::write_regions_png(path, frame, stride, width, height, engine.regions())?;
+        info!("Кадр с регионами сохранён в {}", path);
+        return Ok(());
     }
 
-    // 5) Нижняя левая группа: слева → направо
-    if config.bottom_left_led_count > 0 {
-        let seg_w = left_group_width as f32 / config.bottom_left_led_count as f32;
-        for i in 0..config.bottom_left_led_count {
-            let x1 = (i as f32 * seg_w).round() as usize;
-            let x2 = ((i + 1) as f32 * seg_w).round() as usize;
-            regions.push(LedRegion {
-                x1: x1.min(width),
-                y1: height.saturating_sub(pixel_thickness),
-                x2: x2.min(width),
-                y2: height,
-            });
-        }
+    if let Action::Bench(frames) = cli.action {
+        return bench::run(&config, &mut capturer, frames);
     }
 
-    regions
-}
+    info!("Светодиодов: {}, вывод: {:?}, {} кадров/с", led_count, config.output.mode, config.fps);
 
-/// Предварительный расчёт индексов для каждого региона.
-/// Для каждого пикселя в регионе вычисляем смещение в буфере кадра.
-/// Каждый пиксель занимает 4 байта (BGRA).
-fn precompute_region_indices(regions: &[LedRegion], width: usize) -> Vec<Vec<usize>> {
-    regions
-        .iter()
-        .map(|region| {
-            let mut indices = Vec::new();
-            for y in region.y1..region.y2 {
-                // Вычисляем базовое смещение для строки
-                let row_base = y * width * 4;
-                for x in region.x1..region.x2 {
-                    indices.push(row_base + x * 4);
-                }
+    // 4. Открытие последовательного порта для Arduino (или портов всех [[strips]]). Кадры
+    // на них отправляют отдельные потоки, чтобы медленный порт не задерживал захват
+    let mut outputs = match Outputs::open(&config, led_count) {
+        Ok(outputs) => outputs,
+        Err(_) => {
+            if let Some(options) = &cli.daemon {
+                daemon::remove_pid_file(options);
             }
-            indices
-        })
-        .collect()
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Чтение настроек
-    let config_data = std::fs::read_to_string("config.toml")?;
-    let config: AmbilightConfig = toml::from_str(&config_data)?;
-    println!("Настройки: {:#?}", config);
-
-    // 2. Настройка захвата экрана
-    let display = Display::primary()?;
-    let mut capturer = Capturer::new(display)?;
-    let (width, height) = (capturer.width(), capturer.height());
-    println!("Экран: {}x{}", width, height);
-
-    // 3. Открытие последовательного порта для Arduino
-    let mut port = serialport::new(&config.port_name, config.baud_rate)
-        .timeout(Duration::from_millis(10))
-        .open()
-        .expect("Не удалось открыть порт");
-
-    // 4. Генерация регионов и их оптимизация
-    let mut led_regions = create_led_regions(&config, width, height);
-    if config.invert_direction {
-        led_regions.reverse();
+            std::process::exit(1);
+        }
+    };
+    if config.output.mode == OutputMode::WledUdp && led_count > output::DRGB_MAX_LEDS {
+        warn!("WLED DRGB принимает не больше {} светодиодов, остальные не выводятся", output::DRGB_MAX_LEDS);
     }
-    // Предварительный расчёт смещений (индексов) для каждого региона
-    let precomputed_indices = precompute_region_indices(&led_regions, width);
 
-    // Предвычисление множителей для баланса белого
-    let (r_mult, g_mult, b_mult) =
-        color_temperature_to_rgb_multipliers(config.white_balance_temperature);
+    // Панели Nanoleaf повторяют цвета выбранных светодиодов
+    let mut nanoleaf = config.nanoleaf.as_ref().map(Nanoleaf::connect).transpose()?;
 
-    // Предвычисление яркости
-    let brightness = (config.brightness as f32) / 100.0;
+    // Состояние, изменяемое через сервер управления
+    let state = Arc::new(Mutex::new(engine.state()));
+    // Настройка читается при запуске: клавиша регистрируется один раз
+    if let Some(text) = &config.pause_hotkey {
+        match hotkey::parse_hotkey(text) {
+            Ok(key) => hotkey::spawn_listener(key, Arc::clone(&state)),
+            Err(e) => error!("{}", e),
+        }
+    }
+    let shared_config = Arc::new(Mutex::new(config.clone()));
+    if let Some(port) = config.control_port {
+        control::spawn_server(
+            port,
+            ControlContext {
+                state: Arc::clone(&state),
+                config: Arc::clone(&shared_config),
+                config_path: config_path.to_string(),
+            },
+        )?;
+    }
 
-    // Выделение буфера для формирования пакета Adalight
-    let mut msg_buffer = Vec::with_capacity(3 + 3 * precomputed_indices.len());
+    // Состояние для HTTP-сервера; изменённые им настройки применяются как перечитанные
+    let config_updated = Arc::new(AtomicBool::new(false));
+    let http_status = match config.http_port {
+        Some(port) => {
+            let status = Arc::new(Mutex::new(Status::default()));
+            http::spawn_server(
+                &config.http_bind,
+                port,
+                HttpContext {
+                    status: Arc::clone(&status),
+                    config: Arc::clone(&shared_config),
+                    config_updated: Arc::clone(&config_updated),
+                },
+            )?;
+            Some(status)
+        }
+        None => None,
+    };
+
+    // Цвета ленты для MJPEG-потока
+    let stream_colors = match config.stream_port {
+        Some(port) => {
+            let colors = Arc::new(Mutex::new(Vec::new()));
+            stream::spawn_server(&config.http_bind, port, config.stream_fps, Arc::clone(&colors))?;
+            Some(colors)
+        }
+        None => None,
+    };
 
     // Счётчик FPS
     let mut frame_count = 0;
     let mut fps_timer = Instant::now();
 
     // Заданная длительность кадра
-    let frame_duration = Duration::from_millis(1000 / config.fps as u64);
+    let mut frame_duration = config.frame_duration();
+    let mut pacer = FramePacer::new();
+    let mut capture_rate = CaptureRateMonitor::default();
+
+    // Когда последний раз проверяли, не изменился ли файл настроек
+    let mut config_checked = Instant::now();
+
+    // Сколько кадров ещё отбросить после пересоздания захвата
+    let mut discard_frames = 0;
+
+    // Буфер для кадров, которые приходят не в BGRA
+    let mut converted_frame = Vec::new();
+
+    // Формат кадра проверяется на первом кадре после (пере)создания захвата
+    let mut alpha_checked = false;
+
+    // Лента уже погашена на время паузы
+    let mut blanked = false;
 
     'main_loop: loop {
+        // Ленту гасит поток вывода, когда завершается
+        if shutdown::stop_requested() {
+            break;
+        }
+
+        // Перечитываем настройки, если файл изменился или их поменяли через HTTP;
+        // при ошибке остаются прежние
+        let http_update = config_updated.swap(false, Ordering::Relaxed);
+        if http_update || config_checked.elapsed() >= CONFIG_POLL_INTERVAL {
+            config_checked = Instant::now();
+            let modified = config::modified_time(config_path);
+            if http_update || modified != config_modified {
+                config_modified = modified;
+                let source = if http_update {
+                    Ok(shared_config.lock().unwrap().clone())
+                } else {
+                    config::load_config(config_path, ConfigErrorPolicy::Fail)
+                };
+                let reloaded = source.and_then(|new_config| {
+                    // Область активного окна задаёт отслеживание фокуса, иначе — capture_*
+                    let area = focus_tracker.is_some().then(|| engine.capture_area());
+                    let prepared = engine.prepare(new_config, area)?;
+                    let extras = ExtraDisplays::open(prepared.config())?;
+                    Ok((prepared, extras))
+                });
+                match reloaded {
+                    Ok((prepared, extras)) => {
+                        let new_config = prepared.config();
+                        let led_count = prepared.led_count() + extras.led_count();
+                        let checked = match led_count {
+                            0 => Err(NO_LEDS_ERROR.to_string()),
+                            _ => segments::validate_segments(&new_config.segments, led_count)
+                                .and_then(|()| remap::validate_remap(&new_config.led_remap, led_count))
+                                .and_then(|()| strips::validate_strips(&new_config.strips, led_count))
+                                .and_then(|()| new_config.output.validate_universes(led_count))
+                                .and_then(|()| outputs.check_reload(new_config)),
+                        };
+                        match checked {
+                            Ok(()) => {
+                                outputs.reconfigure(new_config);
+                                config = new_config.clone();
+                                engine.reconfigure(prepared);
+                                extra_displays = extras;
+                                focus_interval = Duration::from_millis(config.focus_poll_interval_ms);
+                                if config.follow_window_focus != focus_tracker.is_some() {
+                                    focus_tracker = config
+                                        .follow_window_focus
+                                        .then(|| FocusTracker::new(focus_interval, engine.capture_area()));
+                                }
+                                frame_duration = config.frame_duration();
+                                {
+                                    // Режим и пауза остаются, остальное — из новых настроек
+                                    let mut state = state.lock().unwrap();
+                                    *state = RuntimeState { mode: state.mode, paused: state.paused, ..engine.state() };
+                                }
+                                *shared_config.lock().unwrap() = config.clone();
+                                if http_update {
+                                    info!("Настройки изменены через HTTP");
+                                } else {
+                                    info!("Настройки перечитаны из {}", config_path);
+                                }
+                            }
+                            Err(e) => warn!("Настройки из {} не применены: {}", config_path, e),
+                        }
+                    }
+                    Err(e) => warn!("Не удалось перечитать {}: {}. Остаются прежние настройки", config_path, e),
+                }
+            }
+        }
+
+        // Пауза: лента гасится один раз, а порт остаётся открытым, чтобы снять паузу сразу
+        let current = *state.lock().unwrap();
+        engine.set_state(current);
+        if current.paused {
+            if !blanked {
+                outputs.blank();
+                blanked = true;
+            }
+            thread::sleep(PAUSE_POLL_INTERVAL);
+            continue 'main_loop;
+        }
+        blanked = false;
+
         // Фиксируем время начала обработки кадра (включая ожидание нового кадра)
         let frame_start = Instant::now();
 
-        // 5. Захват кадра: ждем, пока кадр не станет доступным
-        let frame = loop {
-            match capturer.frame() {
-                Ok(frame) => break frame,
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // Короткий sleep, чтобы не грузить процессор
-                    thread::sleep(Duration::from_millis(1));
-                },
-                Err(e) => {
-                    eprintln!("Ошибка захвата: {}", e);
-                    thread::sleep(frame_duration);
+        let colors = match current.mode {
+            Mode::Screen => {
+                // 5. Захват кадра: ждем, пока кадр не станет доступным.
+                // Сбрасывать здесь нечего: scrap не копит кадры, а отдаёт текущее содержимое
+                // экрана (DXGI — последний кадр рабочего стола, X11 — снимок в момент вызова).
+                // Отставание копилось в очереди потока вывода, поэтому устаревшие кадры
+                // отбрасываются там: Writer::send заменяет ещё не отправленный кадр
+                let wait_start = Instant::now();
+                let mut backoff = Duration::from_millis(1);
+                let frame = loop {
+                    match capturer.frame() {
+                        // Кадр не того размера: разрешение сменилось, а захват ещё старый.
+                        // Читать такой кадр по старым регионам нельзя — пересоздаём захват
+                        Ok(frame) if !frame_fits(frame.len(), width, height, config.pixel_format) => {
+                            warn!(
+                                "Размер кадра ({} байт) не соответствует {}x{}, захват пересоздаётся",
+                                frame.len(),
+                                width,
+                                height
+                            );
+                            break None;
+                        }
+                        Ok(frame) => break Some(frame),
+                        Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                            let waited = wait_start.elapsed();
+                            if waited >= CAPTURE_STALL_TIMEOUT {
+                                warn!("Новых кадров нет {} с, захват пересоздаётся", waited.as_secs());
+                                break None;
+                            }
+                            thread::sleep(backoff);
+                            // Кадр опаздывает дольше бюджета — экран обновляется редко,
+                            // поэтому опрашиваем всё реже, чтобы не грузить процессор
+                            if waited > frame_duration {
+                                backoff = (backoff * 2).min(CAPTURE_BACKOFF_MAX);
+                            }
+                        },
+                        Err(e) => {
+                            error!("Ошибка захвата: {}", e);
+                            break None;
+                        }
+                    }
+                };
+
+                let Some(frame) = frame else {
+                    // Захват мог потеряться из-за смены разрешения или дисплея — пересоздаём его
+                    match open_capturer(&config) {
+                        Ok(new_capturer) => {
+                            capturer = new_capturer;
+                            // Даём дисплею устояться, первые кадры после смены бывают некорректными
+                            thread::sleep(Duration::from_millis(config.reconfigure_settle_ms));
+                            discard_frames = config.reconfigure_discard_frames;
+                            alpha_checked = false;
+                            if (capturer.width(), capturer.height()) != (width, height) {
+                                (width, height) = (capturer.width(), capturer.height());
+                                info!("Новое разрешение экрана: {}x{}", width, height);
+                                engine.resize(width, height);
+                                if let Some(tracker) = &mut focus_tracker {
+                                    *tracker = FocusTracker::new(focus_interval, engine.capture_area());
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("Не удалось пересоздать захват: {}", e);
+                            thread::sleep(frame_duration);
+                        }
+                    }
+                    continue 'main_loop;
+                };
+
+                if discard_frames > 0 {
+                    discard_frames -= 1;
                     continue 'main_loop;
                 }
-            }
-        };
 
-        frame_count += 1;
-        if fps_timer.elapsed() >= Duration::from_secs(1) {
-            println!("FPS захвата экрана: {}", frame_count);
-            frame_count = 0;
-            fps_timer = Instant::now();
-        }
+                frame_count += 1;
+                if fps_timer.elapsed() >= Duration::from_secs(1) {
+                    // Сколько кадров реально ушло на ленту
+                    let output_frames = outputs.take_sent();
+                    if let Some(status) = &http_status {
+                        status.lock().unwrap().fps = output_frames;
+                    }
+                    if config.skip_identical_frames {
+                        info!(
+                            "FPS: {} (захвачено кадров: {}, без пересчёта: {})",
+                            output_frames,
+                            frame_count,
+                            engine.take_skipped_frames()
+                        );
+                    } else {
+                        info!("FPS: {} (захвачено кадров: {})", output_frames, frame_count);
+                    }
+                    if capture_rate.record(frame_count, fps_timer.elapsed(), config.fps) {
+                        warn!(
+                            "Захват отдаёт {} кадров/с при fps = {}. Возможные причины: полноэкранное \
+                             приложение в монопольном режиме, энергосбережение, захват не того дисплея \
+                             или почти неподвижная картинка (новые кадры приходят только при изменениях)",
+                            frame_count, config.fps
+                        );
+                    }
+                    // Посчитанные кадры, которые поток вывода не успел отправить до следующего
+                    let dropped = outputs.take_dropped();
+                    if dropped > 0 {
+                        info!("Вывод не успевает: за секунду заменено неотправленных кадров: {}", dropped);
+                    }
+                    frame_count = 0;
+                    fps_timer = Instant::now();
+                }
 
-        // 6. Параллельный расчёт среднего цвета по регионам
-        let colors: Vec<(u8, u8, u8)> = precomputed_indices.par_iter().map(|indices| {
-            let mut sum_r: u64 = 0;
-            let mut sum_g: u64 = 0;
-            let mut sum_b: u64 = 0;
-            let count = indices.len() as u64;
-            unsafe {
-                let ptr = frame.as_ptr();
-                for &offset in indices {
-                    // Чтение байтов пикселя (порядок: B, G, R, A)
-                    let b = *ptr.add(offset);
-                    let g = *ptr.add(offset + 1);
-                    let r = *ptr.add(offset + 2);
-                    sum_r += r as u64;
-                    sum_g += g as u64;
-                    sum_b += b as u64;
+                // 6. Параллельный расчёт цвета по регионам
+                if let Some(area) = focus_tracker.as_mut().and_then(|tracker| tracker.update(width, height)) {
+                    engine.set_capture_area(area);
                 }
+
+                // Ошибка только пропускает кадр: выход из цикла минуя остановку
+                // вывода оставил бы ленту гореть, а PID-файл — на диске
+                let (frame, stride) =
+                    match format::bgra_frame(&frame, config.pixel_format, width, height, &mut converted_frame) {
+                        Ok(frame) => frame,
+                        Err(e) => {
+                            error!("{}", e);
+                            continue 'main_loop;
+                        }
+                    };
+                if !alpha_checked {
+                    alpha_checked = true;
+                    if color::alpha_varies(frame, stride, engine.regions()) {
+                        warn!(
+                            "Альфа-канал кадра непостоянен, формат захвата может быть не {:?}: \
+                             проверьте pixel_format",
+                            config.pixel_format
+                        );
+                    }
+                }
+                let mut colors = engine.sample_frame(frame);
+                if !extra_displays.is_empty() {
+                    let sampling = engine::sampling(&config);
+                    extra_displays.append_colors(&mut colors, config.pixel_format, sampling, engine.pipeline());
+                    if config.invert_direction {
+                        colors.reverse();
+                    }
+                }
+                colors
             }
-            if count == 0 {
-                return (0, 0, 0);
-            }
+            Mode::Static | Mode::Off => engine.fill_colors(engine.regions().len() + extra_displays.led_count()),
+        };
+        engine.set_output_error(outputs.error());
+        let colors = engine.process_colors(colors);
 
-            let avg_r = (sum_r / count) as u8;
-            let avg_g = (sum_g / count) as u8;
-            let avg_b = (sum_b / count) as u8;
-
-            // Применяем гамма-коррекцию
-            let mut r = 255.0 * ((avg_r as f32 / 255.0).powf(config.gamma));
-            let mut g = 255.0 * ((avg_g as f32 / 255.0).powf(config.gamma));
-            let mut b = 255.0 * ((avg_b as f32 / 255.0).powf(config.gamma));
-
-            // Применяем баланс белого
-            r = r * r_mult;
-            g = g * g_mult;
-            b = b * b_mult;
-
-            // Применяем яркость
-            r = (r * brightness).min(255.0);
-            g = (g * brightness).min(255.0);
-            b = (b * brightness).min(255.0);
-
-            (r as u8, g as u8, b as u8)
-        }).collect();
-
-        // 7. Формирование пакета Adalight
-        msg_buffer.clear();
-        msg_buffer.extend_from_slice(b"Ada");
-        let n = colors.len() * 3;
-        let hi = (n >> 8) as u8;
-        let lo = (n & 0xFF) as u8;
-        let chk = hi ^ lo ^ 0x55;
-        msg_buffer.extend_from_slice(&[hi, lo, chk]);
-        for &(r, g, b) in &colors {
-            msg_buffer.push(r);
-            msg_buffer.push(g);
-            msg_buffer.push(b);
+        if let Some(stream_colors) = &stream_colors {
+            stream_colors.lock().unwrap().clone_from(&colors);
+        }
+        if let Some(status) = &http_status {
+            let mut status = status.lock().unwrap();
+            status.led_count = colors.len();
+            status.colors.clone_from(&colors);
         }
 
-        // let start_timer = Instant::now();
-        if let Err(e) = port.write_all(&msg_buffer) {
-            eprintln!("Ошибка отправки: {}", e);
+        if let Some(nanoleaf) = &mut nanoleaf
+            && let Err(e) = nanoleaf.send(&colors)
+        {
+            error!("Ошибка отправки на Nanoleaf: {}", e);
         }
+        outputs.send(colors);
 
         // 8. Вычисляем общее время, затраченное на получение и обработку кадра,
         // и ждём остаток до завершения заданного периода кадра.
-        let elapsed = frame_start.elapsed();
-        if elapsed < frame_duration {
-            thread::sleep(frame_duration - elapsed);
+        let processing = frame_start.elapsed();
+        debug!("Время кадра: {:?}", processing);
+        let wait = pacer.record(processing, frame_duration);
+        if !wait.is_zero() {
+            thread::sleep(wait);
         }
     }
+
+    outputs.stop();
+    info!("Остановка по запросу, лента погашена");
+
+    if let Some(options) = &cli.daemon {
+        daemon::remove_pid_file(options);
+    }
+    Ok(())
 }
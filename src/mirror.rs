@@ -0,0 +1,107 @@
+use crate::regions::{LedRegion, Side};
+use serde::{Deserialize, Serialize};
+
+/// Зеркальный режим: одна половина ленты повторяет другую, отражённую слева направо.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorMode {
+    #[default]
+    Off,
+    /// Левая половина получает отражение цветов правой
+    RightToLeft,
+    /// Правая половина получает отражение цветов левой
+    LeftToRight,
+}
+
+fn center(region: &LedRegion) -> (f32, f32) {
+    ((region.x1 + region.x2) as f32 / 2.0, (region.y1 + region.y2) as f32 / 2.0)
+}
+
+/// Сторона, на которую переходит регион при отражении слева направо.
+fn mirrored_side(side: Side) -> Side {
+    match side {
+        Side::Left => Side::Right,
+        Side::Right => Side::Left,
+        side => side,
+    }
+}
+
+/// Для каждого светодиода — индекс светодиода, чей цвет он повторяет (`None` — свой цвет).
+/// Источник — ближайший к отражённому центру регион зеркальной стороны: так отражение
+/// работает и при разном числе светодиодов слева и справа.
+pub fn mirror_sources(regions: &[LedRegion], mode: MirrorMode) -> Vec<Option<usize>> {
+    let (Some(left), Some(right)) =
+        (regions.iter().map(|r| r.x1).min(), regions.iter().map(|r| r.x2).max())
+    else {
+        return Vec::new();
+    };
+    let axis = (left + right) as f32 / 2.0;
+    // Положительный знак — правая половина кадра, отрицательный — левая
+    let half = |region: &LedRegion| (center(region).0 - axis).signum();
+    let target_half = match mode {
+        MirrorMode::Off => return vec![None; regions.len()],
+        MirrorMode::RightToLeft => -1.0,
+        MirrorMode::LeftToRight => 1.0,
+    };
+
+    regions
+        .iter()
+        .map(|target| {
+            if half(target) != target_half {
+                return None;
+            }
+            let (x, y) = center(target);
+            let mirrored = (2.0 * axis - x, y);
+            let side = target.edge.map(mirrored_side);
+            let distance = |region: &LedRegion| {
+                let (cx, cy) = center(region);
+                (cx - mirrored.0).powi(2) + (cy - mirrored.1).powi(2)
+            };
+            regions
+                .iter()
+                .enumerate()
+                .filter(|(_, region)| region.edge == side && half(region) == -target_half)
+                .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+                .map(|(index, _)| index)
+        })
+        .collect()
+}
+
+/// Заменяет цвета отражаемой половины отражением другой половины.
+pub fn apply_mirror(colors: &mut [(u8, u8, u8)], regions: &[LedRegion], mode: MirrorMode) {
+    if mode == MirrorMode::Off {
+        return;
+    }
+    let original = colors.to_vec();
+    for (color, source) in colors.iter_mut().zip(mirror_sources(regions, mode)) {
+        if let Some(source) = source {
+            *color = original[source];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn left_side_repeats_right_side_at_the_same_height() {
+        let region = |x1, y1, edge| LedRegion { x1, y1, x2: x1 + 10, y2: y1 + 50, edge: Some(edge) };
+        // Правая сторона снизу вверх, верх справа налево, левая сторона сверху вниз
+        let regions = [
+            region(90, 50, Side::Right),
+            region(90, 0, Side::Right),
+            region(60, 0, Side::Top),
+            region(30, 0, Side::Top),
+            region(0, 0, Side::Left),
+            region(0, 50, Side::Left),
+        ];
+        let mut colors = [(1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 0, 0), (5, 0, 0), (6, 0, 0)];
+        apply_mirror(&mut colors, &regions, MirrorMode::RightToLeft);
+        assert_eq!(colors, [(1, 0, 0), (2, 0, 0), (3, 0, 0), (3, 0, 0), (2, 0, 0), (1, 0, 0)]);
+
+        let mut colors = [(1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 0, 0), (5, 0, 0), (6, 0, 0)];
+        apply_mirror(&mut colors, &regions, MirrorMode::LeftToRight);
+        assert_eq!(colors, [(6, 0, 0), (5, 0, 0), (4, 0, 0), (4, 0, 0), (5, 0, 0), (6, 0, 0)]);
+    }
+}
@@ -0,0 +1,126 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::Duration;
+
+/// Порт HTTP API панелей Nanoleaf.
+const API_PORT: u16 = 16021;
+/// Порт, на который панели принимают кадры внешнего управления.
+const STREAM_PORT: u16 = 60222;
+
+/// Панель Nanoleaf и светодиод, цвет которого она повторяет.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NanoleafPanel {
+    pub panel_id: u16,
+    /// Номер светодиода в порядке ленты
+    pub led: usize,
+}
+
+/// Настройки вывода на панели Nanoleaf (`[nanoleaf]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NanoleafConfig {
+    pub ip: String,
+    /// Токен авторизации HTTP API
+    pub token: String,
+    /// Время перехода к новому цвету в десятых долях секунды
+    #[serde(default = "default_transition_time")]
+    pub transition_time: u16,
+    #[serde(default)]
+    pub panels: Vec<NanoleafPanel>,
+}
+
+fn default_transition_time() -> u16 {
+    1
+}
+
+/// Вывод цветов на панели Nanoleaf по протоколу внешнего управления (UDP, версия 2).
+pub struct Nanoleaf {
+    socket: UdpSocket,
+    panels: Vec<NanoleafPanel>,
+    transition_time: u16,
+    buffer: Vec<u8>,
+}
+
+impl Nanoleaf {
+    /// Включает на панелях режим внешнего управления через HTTP API и готовит UDP-сокет.
+    pub fn connect(config: &NanoleafConfig) -> io::Result<Self> {
+        enable_external_control(config)?;
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((config.ip.as_str(), STREAM_PORT))?;
+        info!("Nanoleaf {}: внешнее управление включено, панелей: {}", config.ip, config.panels.len());
+        Ok(Nanoleaf {
+            socket,
+            panels: config.panels.clone(),
+            transition_time: config.transition_time,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Отправляет цвета панелей одним кадром.
+    pub fn send(&mut self, colors: &[(u8, u8, u8)]) -> io::Result<()> {
+        build_frame(&self.panels, colors, self.transition_time, &mut self.buffer);
+        self.socket.send(&self.buffer)?;
+        Ok(())
+    }
+}
+
+/// PUT /api/v1/<token>/effects с командой `extControl` версии 2.
+fn enable_external_control(config: &NanoleafConfig) -> io::Result<()> {
+    let body = r#"{"write":{"command":"display","animType":"extControl","extControlVersion":"v2"}}"#;
+    let request = format!(
+        "PUT /api/v1/{}/effects HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        config.token,
+        config.ip,
+        API_PORT,
+        body.len(),
+        body
+    );
+
+    let mut stream = TcpStream::connect((config.ip.as_str(), API_PORT))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.write_all(request.as_bytes())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") | Some("204") => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "Nanoleaf не включил внешнее управление: {}",
+            status_line
+        ))),
+    }
+}
+
+/// Кадр версии 2: число панелей (2 байта), затем для каждой панели её id (2 байта),
+/// R, G, B, W (не используется) и время перехода (2 байта). Все числа big-endian.
+fn build_frame(panels: &[NanoleafPanel], colors: &[(u8, u8, u8)], transition_time: u16, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.extend_from_slice(&(panels.len() as u16).to_be_bytes());
+    for panel in panels {
+        let (r, g, b) = colors.get(panel.led).copied().unwrap_or((0, 0, 0));
+        buf.extend_from_slice(&panel.panel_id.to_be_bytes());
+        buf.extend_from_slice(&[r, g, b, 0]);
+        buf.extend_from_slice(&transition_time.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_uses_v2_layout() {
+        let panels = [
+            NanoleafPanel { panel_id: 0x1234, led: 1 },
+            NanoleafPanel { panel_id: 7, led: 99 },
+        ];
+        let mut buf = Vec::new();
+        build_frame(&panels, &[(1, 2, 3), (10, 20, 30)], 5, &mut buf);
+        assert_eq!(
+            buf,
+            [0, 2, 0x12, 0x34, 10, 20, 30, 0, 0, 5, 0, 7, 0, 0, 0, 0, 0, 5]
+        );
+    }
+}
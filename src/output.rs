@@ -0,0 +1,146 @@
+use crate::ddp;
+use crate::dmx::{self, DmxProtocol};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::net::UdpSocket;
+
+/// Номер протокола DRGB в реальном времени WLED.
+const DRGB_PROTOCOL: u8 = 2;
+/// Больше светодиодов в один пакет DRGB не помещается.
+pub const DRGB_MAX_LEDS: usize = 490;
+
+/// Куда отправляются цвета.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Arduino по последовательному порту (`port_name`, `baud_rate`), протокол Adalight
+    #[default]
+    Serial,
+    /// Контроллер WLED по Wi-Fi, протокол UDP DRGB
+    WledUdp,
+    /// DMX-контроллер по Art-Net
+    #[serde(rename = "artnet")]
+    ArtNet,
+    /// DMX-контроллер по E1.31 (sACN)
+    Sacn,
+    /// Контроллер по DDP (WLED, ESPixelStick и другие)
+    Ddp,
+}
+
+/// Настройки вывода (`[output]`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    #[serde(default)]
+    pub mode: OutputMode,
+    /// IP-адрес или имя контроллера WLED
+    #[serde(default)]
+    pub wled_address: String,
+    #[serde(default = "default_wled_port")]
+    pub wled_port: u16,
+    /// Через сколько секунд без пакетов WLED вернётся к своему эффекту (255 — никогда)
+    #[serde(default = "default_wled_timeout")]
+    pub wled_timeout: u8,
+    /// IP-адрес DMX-контроллера для Art-Net и sACN
+    #[serde(default)]
+    pub dmx_address: String,
+    /// Порт контроллера (по умолчанию 6454 для Art-Net и 5568 для sACN)
+    #[serde(default)]
+    pub dmx_port: Option<u16>,
+    /// Первый универсум (по умолчанию 0 для Art-Net и 1 для sACN)
+    #[serde(default)]
+    pub dmx_universe: Option<u16>,
+    /// IP-адрес или имя контроллера DDP
+    #[serde(default)]
+    pub ddp_address: String,
+    #[serde(default = "default_ddp_port")]
+    pub ddp_port: u16,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            mode: OutputMode::Serial,
+            wled_address: String::new(),
+            wled_port: default_wled_port(),
+            wled_timeout: default_wled_timeout(),
+            dmx_address: String::new(),
+            dmx_port: None,
+            dmx_universe: None,
+            ddp_address: String::new(),
+            ddp_port: default_ddp_port(),
+        }
+    }
+}
+
+fn default_wled_port() -> u16 {
+    21324
+}
+
+impl OutputConfig {
+    /// Для Art-Net и sACN проверяет, что ленте из `led_count` светодиодов хватает универсумов.
+    pub fn validate_universes(&self, led_count: usize) -> Result<(), String> {
+        let (protocol, default_universe) = match self.mode {
+            OutputMode::ArtNet => (DmxProtocol::ArtNet, 0),
+            OutputMode::Sacn => (DmxProtocol::Sacn, 1),
+            _ => return Ok(()),
+        };
+        dmx::validate_universes(protocol, self.dmx_universe.unwrap_or(default_universe), led_count)
+    }
+}
+
+fn default_wled_timeout() -> u8 {
+    2
+}
+
+fn default_ddp_port() -> u16 {
+    ddp::DDP_PORT
+}
+
+/// Отправка пакетов на WLED: каждый `write` уходит одной UDP-датаграммой.
+pub struct WledUdp {
+    socket: UdpSocket,
+}
+
+impl WledUdp {
+    pub fn connect(config: &OutputConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect((config.wled_address.as_str(), config.wled_port))?;
+        Ok(WledUdp { socket })
+    }
+}
+
+impl Write for WledUdp {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.socket.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Пакет DRGB: номер протокола, таймаут в секундах и тройки R, G, B.
+/// Светодиоды сверх [`DRGB_MAX_LEDS`] отбрасываются.
+pub fn build_drgb_packet(colors: &[(u8, u8, u8)], timeout: u8, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.push(DRGB_PROTOCOL);
+    buf.push(timeout);
+    for &(r, g, b) in colors.iter().take(DRGB_MAX_LEDS) {
+        buf.extend_from_slice(&[r, g, b]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drgb_packet_has_header_and_rgb_triples() {
+        let mut buf = Vec::new();
+        build_drgb_packet(&[(1, 2, 3), (4, 5, 6)], 2, &mut buf);
+        assert_eq!(buf, [2, 2, 1, 2, 3, 4, 5, 6]);
+
+        build_drgb_packet(&vec![(9, 9, 9); DRGB_MAX_LEDS + 10], 255, &mut buf);
+        assert_eq!(buf.len(), 2 + 3 * DRGB_MAX_LEDS);
+    }
+}
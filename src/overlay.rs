@@ -0,0 +1,104 @@
+use crate::color::{BYTES_PER_PIXEL, CHANNEL_B, CHANNEL_G, CHANNEL_R};
+use crate::regions::LedRegion;
+use image::{Rgb, RgbImage};
+
+/// Цифры 3x5 для номеров светодиодов: по строке на байт, старший из трёх битов — левый.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+/// Соседние регионы обводятся разными цветами, чтобы были видны их границы.
+const OUTLINE_COLORS: [Rgb<u8>; 2] = [Rgb([255, 230, 0]), Rgb([0, 220, 255])];
+
+fn fill(image: &mut RgbImage, x1: u32, y1: u32, x2: u32, y2: u32, color: Rgb<u8>) {
+    for y in y1..y2.min(image.height()) {
+        for x in x1..x2.min(image.width()) {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+/// Пишет число белыми цифрами на чёрной подложке, начиная с `(x, y)`.
+fn draw_number(image: &mut RgbImage, number: usize, x: u32, y: u32, scale: u32) {
+    let text = number.to_string();
+    let width = (text.len() as u32 * 4 + 1) * scale;
+    fill(image, x, y, x + width, y + 7 * scale, Rgb([0, 0, 0]));
+    for (position, digit) in text.bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = x + (position as u32 * 4 + 1) * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    let (px, py) = (left + column * scale, y + (row as u32 + 1) * scale);
+                    fill(image, px, py, px + scale, py + scale, Rgb([255, 255, 255]));
+                }
+            }
+        }
+    }
+}
+
+/// Рисует поверх кадра BGRA контуры регионов и номера светодиодов:
+/// видно, какие пиксели экрана усредняет каждый светодиод.
+pub fn render_regions(frame: &[u8], stride: usize, width: usize, height: usize, regions: &[LedRegion]) -> RgbImage {
+    let mut image = RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let offset = y as usize * stride + x as usize * BYTES_PER_PIXEL;
+        Rgb([frame[offset + CHANNEL_R], frame[offset + CHANNEL_G], frame[offset + CHANNEL_B]])
+    });
+    // На экране 1080p цифры выходят высотой около 20 пикселей
+    let scale = (height as u32 / 270).max(1);
+    for (index, region) in regions.iter().enumerate() {
+        let (x1, y1, x2, y2) = (region.x1 as u32, region.y1 as u32, region.x2 as u32, region.y2 as u32);
+        if x1 >= x2 || y1 >= y2 {
+            continue;
+        }
+        let color = OUTLINE_COLORS[index % OUTLINE_COLORS.len()];
+        fill(&mut image, x1, y1, x2, y1 + 1, color);
+        fill(&mut image, x1, y2 - 1, x2, y2, color);
+        fill(&mut image, x1, y1, x1 + 1, y2, color);
+        fill(&mut image, x2 - 1, y1, x2, y2, color);
+        draw_number(&mut image, index, x1 + 1, y1 + 1, scale);
+    }
+    image
+}
+
+/// Сохраняет кадр с нарисованными регионами в PNG.
+pub fn write_regions_png(
+    path: &str,
+    frame: &[u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+    regions: &[LedRegion],
+) -> image::ImageResult<()> {
+    render_regions(frame, stride, width, height, regions).save_with_format(path, image::ImageFormat::Png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_outlined_over_the_frame() {
+        // Серый кадр 40x30 в BGRA с одним регионом посередине
+        let (width, height) = (40, 30);
+        let frame = [10, 20, 30, 255].repeat(width * height);
+        let region = LedRegion { x1: 10, y1: 10, x2: 30, y2: 25, edge: None };
+        let image = render_regions(&frame, width * BYTES_PER_PIXEL, width, height, &[region]);
+
+        assert_eq!(image.get_pixel(0, 0), &Rgb([30, 20, 10]));
+        assert_eq!(image.get_pixel(20, 24), &OUTLINE_COLORS[0]);
+        assert_eq!(image.get_pixel(29, 20), &OUTLINE_COLORS[0]);
+        // Внутри региона под номером — исходный кадр
+        assert_eq!(image.get_pixel(20, 20), &Rgb([30, 20, 10]));
+        // Номер «0»: верхняя строка цифры белая
+        assert_eq!(image.get_pixel(12, 12), &Rgb([255, 255, 255]));
+    }
+}
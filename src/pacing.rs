@@ -0,0 +1,100 @@
+use log::warn;
+use std::time::{Duration, Instant};
+
+/// Вес нового кадра в скользящем среднем времени обработки.
+const AVERAGE_WEIGHT: f32 = 0.1;
+/// Сколько секунд подряд среднее должно превышать бюджет кадра до предупреждения.
+const SLOW_WARN_SECS: u32 = 5;
+
+/// Следит за временем обработки кадра и предупреждает, если заданный `fps` недостижим.
+pub struct FramePacer {
+    /// Скользящее среднее времени обработки кадра, секунды
+    average: Option<f32>,
+    slow_seconds: u32,
+    last_check: Instant,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        FramePacer { average: None, slow_seconds: 0, last_check: Instant::now() }
+    }
+
+    /// Учитывает время обработки кадра и возвращает, сколько ждать до следующего.
+    pub fn record(&mut self, processing: Duration, frame_duration: Duration) -> Duration {
+        let seconds = processing.as_secs_f32();
+        let average = match self.average {
+            Some(average) => average + AVERAGE_WEIGHT * (seconds - average),
+            None => seconds,
+        };
+        self.average = Some(average);
+
+        // Без ограничения FPS (нулевой бюджет) предупреждать не о чем
+        if !frame_duration.is_zero() && self.last_check.elapsed() >= Duration::from_secs(1) {
+            self.last_check = Instant::now();
+            if average > frame_duration.as_secs_f32() {
+                self.slow_seconds += 1;
+                if self.slow_seconds == SLOW_WARN_SECS {
+                    warn!(
+                        "Обработка кадра занимает в среднем {:.1} мс при бюджете {:.1} мс, \
+                         попробуйте fps = {}",
+                        average * 1000.0,
+                        frame_duration.as_secs_f32() * 1000.0,
+                        ((1.0 / average) as u32).max(1)
+                    );
+                }
+            } else {
+                self.slow_seconds = 0;
+            }
+        }
+
+        frame_duration.saturating_sub(processing)
+    }
+}
+
+/// Доля заданного `fps`, ниже которой захват считается подозрительно медленным.
+const LOW_CAPTURE_RATIO: f64 = 0.5;
+
+/// Следит, сколько кадров реально отдаёт захват: система может тихо замедлить его
+/// (DWM без окна в фокусе, энергосбережение), и заданный `fps` не достигается.
+#[derive(Default)]
+pub struct CaptureRateMonitor {
+    low_seconds: u32,
+}
+
+impl CaptureRateMonitor {
+    /// Учитывает `captured` кадров за `elapsed` и возвращает `true` один раз, когда захват
+    /// `SLOW_WARN_SECS` секунд подряд медленнее половины `target_fps`.
+    pub fn record(&mut self, captured: usize, elapsed: Duration, target_fps: f64) -> bool {
+        let achieved = captured as f64 / elapsed.as_secs_f64();
+        if target_fps > 0.0 && achieved < target_fps * LOW_CAPTURE_RATIO {
+            self.low_seconds += 1;
+        } else {
+            self.low_seconds = 0;
+        }
+        self.low_seconds == SLOW_WARN_SECS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_capture_rate_warns_once_after_several_seconds() {
+        let mut monitor = CaptureRateMonitor::default();
+        let second = Duration::from_secs(1);
+        let warnings: Vec<bool> = (0..SLOW_WARN_SECS + 2).map(|_| monitor.record(5, second, 30.0)).collect();
+        assert_eq!(warnings.iter().filter(|&&warn| warn).count(), 1);
+        assert!(warnings[SLOW_WARN_SECS as usize - 1]);
+
+        // Нормальная секунда сбрасывает счёт, без ограничения FPS сравнивать не с чем
+        assert!(!monitor.record(20, second, 30.0));
+        assert!(!monitor.record(5, second, 0.0));
+    }
+}
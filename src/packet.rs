@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+/// Как из R, G, B получается белый канал RGBW-ленты.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WhiteExtraction {
+    /// Белый — минимум из R, G, B, который вычитается из цветных каналов:
+    /// яркость та же, а общий для трёх каналов свет даёт белый светодиод
+    #[default]
+    Subtract,
+    /// Белый — минимум из R, G, B, цветные каналы не меняются (ярче, но бледнее)
+    Add,
+}
+
+/// Обрамление данных в пакете для последовательного порта.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    /// Заголовок Adalight из 6 байт перед данными
+    #[default]
+    Adalight,
+    /// Только байты каналов, без заголовка: для простых прошивок, которые читают
+    /// кадр фиксированной длины
+    Raw,
+}
+
+/// Заголовок пакета Adalight по умолчанию.
+pub const ADALIGHT_MAGIC: &str = "Ada";
+/// Начальное значение контрольного байта заголовка по умолчанию.
+pub const ADALIGHT_CHECKSUM_XOR: u8 = 0x55;
+
+/// Формат пакета Adalight: заголовок, порядок байт светодиода и белый канал для RGBW-лент.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelLayout {
+    /// Каналы в порядке отправки: 0 — R, 1 — G, 2 — B, 3 — W
+    order: [usize; 4],
+    white: Option<WhiteExtraction>,
+    magic: [u8; 3],
+    checksum_xor: u8,
+    framing: Framing,
+}
+
+impl Default for ChannelLayout {
+    fn default() -> Self {
+        ChannelLayout {
+            order: [0, 1, 2, 3],
+            white: None,
+            magic: *b"Ada",
+            checksum_xor: ADALIGHT_CHECKSUM_XOR,
+            framing: Framing::Adalight,
+        }
+    }
+}
+
+impl ChannelLayout {
+    /// Разбирает порядок вроде `"GRB"` или `"GRBW"`; `W` обязателен, если задан `white`,
+    /// и недопустим без него.
+    pub fn parse(order: &str, white: Option<WhiteExtraction>) -> Result<Self, String> {
+        let expected = if white.is_some() { "RGBW" } else { "RGB" };
+        let mut layout = ChannelLayout { white, ..ChannelLayout::default() };
+        let mut seen = [false; 4];
+        if order.len() != expected.len() {
+            return Err(format!("channel_order \"{}\" должен быть перестановкой {}", order, expected));
+        }
+        for (slot, letter) in layout.order.iter_mut().zip(order.to_ascii_uppercase().chars()) {
+            match expected.find(letter) {
+                Some(channel) if !seen[channel] => {
+                    seen[channel] = true;
+                    *slot = channel;
+                }
+                _ => return Err(format!("channel_order \"{}\" должен быть перестановкой {}", order, expected)),
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Заменяет заголовок пакета для прошивок, ждущих другое слово вместо `"Ada"`
+    /// или другое начальное значение контрольного байта. Слово — ровно 3 ASCII-символа.
+    pub fn with_header(self, magic: &str, checksum_xor: u8) -> Result<Self, String> {
+        let magic: [u8; 3] = magic
+            .as_bytes()
+            .try_into()
+            .ok()
+            .filter(|_| magic.is_ascii())
+            .ok_or_else(|| format!("magic_word \"{}\" должен состоять ровно из 3 ASCII-символов", magic))?;
+        Ok(ChannelLayout { magic, checksum_xor, ..self })
+    }
+
+    /// С `Framing::Raw` заголовок не отправляется, и `magic_word` с `checksum_xor` не действуют.
+    pub fn with_framing(self, framing: Framing) -> Self {
+        ChannelLayout { framing, ..self }
+    }
+
+    pub fn bytes_per_led(&self) -> usize {
+        if self.white.is_some() { 4 } else { 3 }
+    }
+
+    fn push(&self, (r, g, b): (u8, u8, u8), buf: &mut Vec<u8>) {
+        let channels = match self.white {
+            None => [r, g, b, 0],
+            Some(white) => {
+                let w = r.min(g).min(b);
+                match white {
+                    WhiteExtraction::Subtract => [r - w, g - w, b - w, w],
+                    WhiteExtraction::Add => [r, g, b, w],
+                }
+            }
+        };
+        buf.extend(self.order[..self.bytes_per_led()].iter().map(|&channel| channels[channel]));
+    }
+}
+
+/// Собирает пакет Adalight: `"Ada"`, два байта длины данных (число светодиодов * 3),
+/// контрольный байт `hi ^ lo ^ 0x55` и затем R, G, B каждого светодиода.
+/// Заголовок и начальное значение контрольного байта меняются через `ChannelLayout::with_header`.
+pub fn build_adalight_packet(colors: &[(u8, u8, u8)], buf: &mut Vec<u8>) {
+    build_adalight_packet_with(colors, ChannelLayout::default(), buf);
+}
+
+/// Пакет Adalight с заданным порядком байт; для RGBW длина данных — число светодиодов * 4.
+/// С `Framing::Raw` — только данные, без заголовка.
+pub fn build_adalight_packet_with(colors: &[(u8, u8, u8)], layout: ChannelLayout, buf: &mut Vec<u8>) {
+    buf.clear();
+    if layout.framing == Framing::Adalight {
+        buf.extend_from_slice(&layout.magic);
+        let n = colors.len() * layout.bytes_per_led();
+        let hi = (n >> 8) as u8;
+        let lo = (n & 0xFF) as u8;
+        let chk = hi ^ lo ^ layout.checksum_xor;
+        buf.extend_from_slice(&[hi, lo, chk]);
+    }
+    for &color in colors {
+        layout.push(color, buf);
+    }
+}
+
+/// Через сколько пакетов обязательно отправляется полный кадр,
+/// чтобы прошивка восстановилась после потерянного байта.
+const KEYFRAME_INTERVAL: u32 = 60;
+
+/// Дельта-кодирование для медленных линий: отправляются только изменившиеся светодиоды.
+///
+/// Формат дельта-пакета (требует поддержки в прошивке):
+/// `"AdD"`, два байта числа изменившихся светодиодов `count` (старший, младший),
+/// контрольный байт `hi ^ lo ^ 0x55`, затем `count` записей по 5 байт:
+/// индекс светодиода (старший, младший байт), R, G, B.
+/// Если изменилось больше светодиодов, чем порог, отправляется обычный пакет Adalight.
+#[derive(Default)]
+pub struct DeltaEncoder {
+    last_sent: Vec<(u8, u8, u8)>,
+    packets_since_keyframe: u32,
+    /// Фактически отправлено байт
+    bytes_sent: usize,
+    /// Сколько байт заняли бы полные пакеты
+    bytes_full: usize,
+}
+
+impl DeltaEncoder {
+    /// Формирует в `buf` дельта- или полный пакет относительно последнего отправленного кадра.
+    /// `max_changed = 0` означает порог окупаемости: дельта отправляется, пока она короче
+    /// полного пакета. После успешной отправки нужно вызвать `mark_sent`.
+    pub fn encode(&self, colors: &[(u8, u8, u8)], max_changed: usize, buf: &mut Vec<u8>) {
+        let max_changed = if max_changed == 0 { colors.len() * 3 / 5 } else { max_changed };
+        let changed = if self.last_sent.len() == colors.len() {
+            colors.iter().zip(&self.last_sent).filter(|(new, old)| new != old).count()
+        } else {
+            usize::MAX
+        };
+
+        if changed > max_changed || self.packets_since_keyframe >= KEYFRAME_INTERVAL {
+            build_adalight_packet(colors, buf);
+            return;
+        }
+
+        buf.clear();
+        buf.extend_from_slice(b"AdD");
+        let hi = (changed >> 8) as u8;
+        let lo = (changed & 0xFF) as u8;
+        buf.extend_from_slice(&[hi, lo, hi ^ lo ^ 0x55]);
+        for (index, (new, _)) in colors
+            .iter()
+            .zip(&self.last_sent)
+            .enumerate()
+            .filter(|(_, (new, old))| new != old)
+        {
+            buf.extend_from_slice(&[(index >> 8) as u8, (index & 0xFF) as u8, new.0, new.1, new.2]);
+        }
+    }
+
+    /// Запоминает кадр, который прошивка получила пакетом `packet`.
+    pub fn mark_sent(&mut self, colors: &[(u8, u8, u8)], packet: &[u8]) {
+        if packet.starts_with(b"Ada") {
+            self.packets_since_keyframe = 0;
+        } else {
+            self.packets_since_keyframe += 1;
+        }
+        self.last_sent.clear();
+        self.last_sent.extend_from_slice(colors);
+        self.bytes_sent += packet.len();
+        self.bytes_full += 6 + colors.len() * 3;
+    }
+
+    /// Доля сэкономленного трафика с прошлого вызова, в процентах; сбрасывает счётчики.
+    pub fn take_savings_percent(&mut self) -> f32 {
+        let savings = if self.bytes_full == 0 {
+            0.0
+        } else {
+            100.0 * (1.0 - self.bytes_sent as f32 / self.bytes_full as f32)
+        };
+        self.bytes_sent = 0;
+        self.bytes_full = 0;
+        savings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbw_packet_extracts_white_in_configured_order() {
+        let layout = ChannelLayout::parse("GRBW", Some(WhiteExtraction::Subtract)).unwrap();
+        let mut buf = Vec::new();
+        build_adalight_packet_with(&[(200, 100, 50)], layout, &mut buf);
+        // Длина данных 4, контрольный байт 0 ^ 4 ^ 0x55
+        assert_eq!(buf, [b'A', b'd', b'a', 0, 4, 0x51, 50, 150, 0, 50]);
+
+        assert!(ChannelLayout::parse("RGB", Some(WhiteExtraction::Add)).is_err());
+        assert!(ChannelLayout::parse("RGW", None).is_err());
+        assert!(ChannelLayout::parse("RRB", None).is_err());
+        assert_eq!(ChannelLayout::parse("rgb", None), Ok(ChannelLayout::default()));
+    }
+
+    #[test]
+    fn custom_header_replaces_magic_and_checksum_seed() {
+        let default = ChannelLayout::default();
+        assert_eq!(default.with_header(ADALIGHT_MAGIC, ADALIGHT_CHECKSUM_XOR), Ok(default));
+
+        let layout = default.with_header("Tpm", 0x00).unwrap();
+        let mut buf = Vec::new();
+        build_adalight_packet_with(&[(1, 2, 3)], layout, &mut buf);
+        assert_eq!(buf, [b'T', b'p', b'm', 0, 3, 3, 1, 2, 3]);
+
+        assert!(default.with_header("Adal", 0x55).is_err());
+        assert!(default.with_header("Ад", 0x55).is_err());
+    }
+
+    #[test]
+    fn raw_framing_sends_only_channel_bytes() {
+        let layout = ChannelLayout::parse("GRB", None).unwrap().with_framing(Framing::Raw);
+        let mut buf = Vec::new();
+        build_adalight_packet_with(&[(1, 2, 3), (4, 5, 6)], layout, &mut buf);
+        assert_eq!(buf, [2, 1, 3, 5, 4, 6]);
+    }
+}
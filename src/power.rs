@@ -0,0 +1,59 @@
+/// Оценка тока ленты для ограничения мощности.
+#[derive(Debug, Clone, Copy)]
+pub struct PowerModel {
+    /// Ток одного канала светодиода на полной яркости, мА
+    pub channel_ma: f32,
+    /// Ток погашенного светодиода, мА
+    pub idle_ma: f32,
+}
+
+impl PowerModel {
+    /// Оценочный ток всей ленты, мА.
+    pub fn estimate_ma(&self, colors: &[(u8, u8, u8)]) -> f32 {
+        let channels: u32 = colors.iter().map(|&(r, g, b)| r as u32 + g as u32 + b as u32).sum();
+        colors.len() as f32 * self.idle_ma + channels as f32 / 255.0 * self.channel_ma
+    }
+}
+
+/// Равномерно приглушает все цвета, если оценочный ток превышает `max_ma`.
+/// Соотношение цветов сохраняется. Возвращает применённый множитель.
+pub fn limit_power(colors: &mut [(u8, u8, u8)], model: PowerModel, max_ma: f32) -> f32 {
+    let total = model.estimate_ma(colors);
+    if total <= max_ma {
+        return 1.0;
+    }
+
+    // Ток покоя не зависит от цвета, уменьшить можно только остальную часть
+    let idle = colors.len() as f32 * model.idle_ma;
+    let factor = ((max_ma - idle) / (total - idle)).clamp(0.0, 1.0);
+    for (r, g, b) in colors.iter_mut() {
+        *r = (*r as f32 * factor) as u8;
+        *g = (*g as f32 * factor) as u8;
+        *b = (*b as f32 * factor) as u8;
+    }
+    factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: PowerModel = PowerModel { channel_ma: 20.0, idle_ma: 1.0 };
+
+    #[test]
+    fn white_frame_is_scaled_to_fit_limit() {
+        // 100 белых светодиодов: 100 мА покоя + 6000 мА на цвета
+        let mut colors = vec![(255, 255, 255); 100];
+        let factor = limit_power(&mut colors, MODEL, 3100.0);
+        assert!((factor - 0.5).abs() < 1e-3);
+        assert!(colors.iter().all(|&color| color == (127, 127, 127)));
+        assert!(MODEL.estimate_ma(&colors) <= 3100.0);
+    }
+
+    #[test]
+    fn frame_within_limit_is_untouched() {
+        let mut colors = vec![(10, 200, 30); 10];
+        assert_eq!(limit_power(&mut colors, MODEL, 3100.0), 1.0);
+        assert!(colors.iter().all(|&color| color == (10, 200, 30)));
+    }
+}
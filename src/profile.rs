@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Доля нового кадра в скользящих средних метрик изображения.
+const METRIC_SMOOTHING: f32 = 0.05;
+
+/// Переопределения настроек для одного типа изображения.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    pub gamma: Option<f32>,
+    pub white_balance_temperature: Option<f32>,
+    /// Яркость в процентах от общей
+    pub brightness: Option<usize>,
+    pub smoothing: Option<f32>,
+}
+
+/// Профили, между которыми переключается автоопределение (`[profiles.game]`, `[profiles.movie]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub game: Profile,
+    #[serde(default)]
+    pub movie: Profile,
+}
+
+impl Profiles {
+    pub fn get(&self, kind: ContentKind) -> &Profile {
+        match kind {
+            ContentKind::Game => &self.game,
+            ContentKind::Movie => &self.movie,
+        }
+    }
+}
+
+/// Тип изображения на экране.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentKind {
+    /// Быстрая смена картинки, насыщенные цвета
+    Game,
+    /// Спокойная картинка
+    Movie,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentKind::Game => write!(f, "игра"),
+            ContentKind::Movie => write!(f, "фильм"),
+        }
+    }
+}
+
+/// Пороги автоопределения типа изображения.
+pub struct DetectorThresholds {
+    /// Среднее изменение канала светодиода за кадр (0–255), выше которого изображение считается игрой
+    pub motion: f32,
+    /// Средняя насыщенность (0.0–1.0), выше которой изображение считается игрой
+    pub saturation: f32,
+    /// Насколько (доля порога) метрики должны опуститься ниже порогов для возврата к фильму
+    pub hysteresis: f32,
+    /// Сколько новый тип должен держаться, прежде чем профиль переключится
+    pub hold: Duration,
+}
+
+/// Определяет тип изображения по цветам светодиодов: скорости их изменения и насыщенности.
+pub struct ContentDetector {
+    thresholds: DetectorThresholds,
+    motion: f32,
+    saturation: f32,
+    previous: Vec<(u8, u8, u8)>,
+    current: ContentKind,
+    candidate_since: Option<Instant>,
+}
+
+impl ContentDetector {
+    pub fn new(thresholds: DetectorThresholds) -> Self {
+        ContentDetector {
+            thresholds,
+            motion: 0.0,
+            saturation: 0.0,
+            previous: Vec::new(),
+            current: ContentKind::Movie,
+            candidate_since: None,
+        }
+    }
+
+    /// Текущий тип изображения.
+    pub fn current(&self) -> ContentKind {
+        self.current
+    }
+
+    /// Учитывает новый кадр; возвращает новый тип изображения, если профиль пора сменить.
+    pub fn update(&mut self, colors: &[(u8, u8, u8)]) -> Option<ContentKind> {
+        if colors.is_empty() {
+            return None;
+        }
+
+        if self.previous.len() == colors.len() {
+            let total: u32 = colors
+                .iter()
+                .zip(&self.previous)
+                .map(|(&(r1, g1, b1), &(r2, g2, b2))| {
+                    r1.abs_diff(r2) as u32 + g1.abs_diff(g2) as u32 + b1.abs_diff(b2) as u32
+                })
+                .sum();
+            let frame_motion = total as f32 / (colors.len() * 3) as f32;
+            self.motion += (frame_motion - self.motion) * METRIC_SMOOTHING;
+        }
+        self.previous.clear();
+        self.previous.extend_from_slice(colors);
+
+        let frame_saturation = colors
+            .iter()
+            .map(|&(r, g, b)| {
+                let max = r.max(g).max(b);
+                let min = r.min(g).min(b);
+                if max == 0 { 0.0 } else { (max - min) as f32 / max as f32 }
+            })
+            .sum::<f32>()
+            / colors.len() as f32;
+        self.saturation += (frame_saturation - self.saturation) * METRIC_SMOOTHING;
+
+        let t = &self.thresholds;
+        let detected = match self.current {
+            ContentKind::Movie if self.motion > t.motion || self.saturation > t.saturation => ContentKind::Game,
+            ContentKind::Game
+                if self.motion < t.motion * (1.0 - t.hysteresis)
+                    && self.saturation < t.saturation * (1.0 - t.hysteresis) =>
+            {
+                ContentKind::Movie
+            }
+            current => current,
+        };
+
+        if detected == self.current {
+            self.candidate_since = None;
+            return None;
+        }
+        let since = *self.candidate_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < t.hold {
+            return None;
+        }
+        self.current = detected;
+        self.candidate_since = None;
+        Some(detected)
+    }
+}
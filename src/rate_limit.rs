@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+/// Сколько секунд трафика может накопиться в ведре для всплеска.
+const BURST: Duration = Duration::from_millis(250);
+
+/// Ограничитель скорости вывода по принципу «ведра с токенами» (1 токен = 1 байт).
+/// Пакеты не копятся в очереди: если токенов не хватает, кадр ждёт их, а пришедший
+/// за это время более свежий кадр его заменяет.
+pub struct TokenBucket {
+    bytes_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bytes_per_second: u32) -> Self {
+        TokenBucket {
+            bytes_per_second: bytes_per_second as f64,
+            // Ведро начинает полным: ёмкость станет известна с первым пакетом
+            tokens: f64::INFINITY,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Списывает `bytes` токенов, если их хватает. Ёмкость ведра не меньше одного пакета,
+    /// иначе большой пакет не ушёл бы никогда.
+    pub fn try_consume(&mut self, bytes: usize) -> bool {
+        self.try_consume_at(bytes, Instant::now())
+    }
+
+    fn try_consume_at(&mut self, bytes: usize, now: Instant) -> bool {
+        let capacity = (self.bytes_per_second * BURST.as_secs_f64()).max(bytes as f64);
+        self.tokens = (self.tokens
+            + now.duration_since(self.last_refill).as_secs_f64() * self.bytes_per_second)
+            .min(capacity);
+        self.last_refill = now;
+
+        if self.tokens >= bytes as f64 {
+            self.tokens -= bytes as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Через сколько накопится `bytes` токенов, считая от последнего `try_consume`.
+    pub fn time_until(&self, bytes: usize) -> Duration {
+        Duration::from_secs_f64((bytes as f64 - self.tokens).max(0.0) / self.bytes_per_second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_starts_full_and_refills_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000);
+        bucket.last_refill = start;
+        // Полное ведро — 250 байт: первый пакет уходит сразу, второй ждёт
+        assert!(bucket.try_consume_at(250, start));
+        assert!(!bucket.try_consume_at(125, start));
+        assert_eq!(bucket.time_until(125), Duration::from_millis(125));
+        assert!(bucket.try_consume_at(125, start + Duration::from_millis(125)));
+        // За долгий простой копится не больше ёмкости
+        let later = start + Duration::from_secs(10);
+        assert!(bucket.try_consume_at(250, later));
+        assert!(!bucket.try_consume_at(1, later));
+    }
+
+    #[test]
+    fn oversized_packet_waits_for_a_full_packet_of_tokens() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000);
+        bucket.last_refill = start;
+        assert!(bucket.try_consume_at(600, start));
+        assert!(!bucket.try_consume_at(600, start + Duration::from_millis(300)));
+        assert!(bucket.try_consume_at(600, start + Duration::from_millis(600)));
+    }
+}
@@ -0,0 +1,692 @@
+use crate::config::AmbilightConfig;
+use serde::{Deserialize, Serialize};
+
+/// Раскладка светодиодов.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Лента по периметру экрана
+    #[default]
+    Edges,
+    /// Матрица MxN, на которую выводится уменьшенная копия экрана
+    Matrix,
+    /// Раскладка, импортированная из конфигурации Hyperion
+    Hyperion,
+}
+
+/// Порядок подключения светодиодов матрицы.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatrixWiring {
+    /// Все строки слева направо
+    #[default]
+    Progressive,
+    /// Чётные строки слева направо, нечётные — справа налево («змейка»)
+    Serpentine,
+}
+
+/// Как делить низ экрана между нижними группами светодиодов.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BottomGroupSizing {
+    /// Ширина без `offset` делится между группами пропорционально числу светодиодов
+    #[default]
+    Ratio,
+    /// Шаг светодиода внизу равен шагу верхней стороны; группы прижаты к углам,
+    /// а разрыв между ними — всё, что осталось (не меньше `offset`)
+    Pitch,
+}
+
+/// Сторона экрана, вдоль которой идёт участок ленты.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Толщина регионов стороны: в процентах от размера экрана поперёк стороны (ширины —
+/// для левой и правой, высоты — для верхней и нижней) или в пикселях.
+/// В настройках записывается строкой: `"5%"` или `"40px"`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Thickness {
+    Percent(usize),
+    Pixels(usize),
+}
+
+impl Thickness {
+    /// Толщина в пикселях для экрана размером `across` поперёк стороны.
+    pub fn pixels(self, across: usize) -> usize {
+        match self {
+            Thickness::Percent(percent) => across * percent / 100,
+            Thickness::Pixels(pixels) => pixels.min(across),
+        }
+    }
+}
+
+impl TryFrom<String> for Thickness {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        let invalid = || format!("толщина \"{}\" должна быть вида \"5%\" или \"40px\"", value);
+        if let Some(percent) = value.trim().strip_suffix('%') {
+            let percent: usize = percent.trim().parse().map_err(|_| invalid())?;
+            if percent > 100 {
+                return Err(format!("толщина {} больше 100%", value));
+            }
+            Ok(Thickness::Percent(percent))
+        } else if let Some(pixels) = value.trim().strip_suffix("px") {
+            Ok(Thickness::Pixels(pixels.trim().parse().map_err(|_| invalid())?))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl From<Thickness> for String {
+    fn from(thickness: Thickness) -> String {
+        match thickness {
+            Thickness::Percent(percent) => format!("{}%", percent),
+            Thickness::Pixels(pixels) => format!("{}px", pixels),
+        }
+    }
+}
+
+/// Прямоугольник кадра, по которому строятся регионы (по умолчанию весь экран).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureArea {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Определяем область экрана для одного светодиода
+pub struct LedRegion {
+    pub x1: usize,
+    pub y1: usize,
+    pub x2: usize,
+    pub y2: usize,
+    /// Край экрана, к которому прилегает регион (None — угол, матрица, импорт)
+    pub edge: Option<Side>,
+}
+
+/// Расширяет регион вглубь экрана (от его стороны к центру), пока при шаге выборки `step`
+/// в нём не окажется хотя бы `min_pixels` усредняемых пикселей или он не упрётся в край экрана.
+fn expand_to_min_pixels(
+    mut region: LedRegion,
+    side: Side,
+    min_pixels: usize,
+    step: usize,
+    width: usize,
+    height: usize,
+) -> LedRegion {
+    // Из n пикселей подряд выборка с шагом step берёт ceil(n / step)
+    let sampled = |pixels: usize| pixels.div_ceil(step);
+    let length = match side {
+        Side::Top | Side::Bottom => region.x2 - region.x1,
+        Side::Left | Side::Right => region.y2 - region.y1,
+    };
+    if length == 0 || sampled(region.x2 - region.x1) * sampled(region.y2 - region.y1) >= min_pixels {
+        return region;
+    }
+
+    // Наименьшая толщина, в которой выборка берёт нужное число рядов
+    let thickness = (min_pixels.div_ceil(sampled(length)) - 1) * step + 1;
+    match side {
+        Side::Top => region.y2 = region.y2.max(thickness.min(height)),
+        Side::Bottom => region.y1 = region.y1.min(height.saturating_sub(thickness)),
+        Side::Left => region.x2 = region.x2.max(thickness.min(width)),
+        Side::Right => region.x1 = region.x1.min(width.saturating_sub(thickness)),
+    }
+    region
+}
+
+/// Сдвигает регион от края экрана внутрь на `inset_percent` процентов размера экрана,
+/// не меняя его размеров: так на телевизорах с overscan регион не попадает на чёрную кайму.
+fn inset_region(
+    mut region: LedRegion,
+    side: Side,
+    inset_percent: usize,
+    width: usize,
+    height: usize,
+) -> LedRegion {
+    match side {
+        Side::Top => {
+            let dy = (height * inset_percent / 100).min(height - region.y2);
+            region.y1 += dy;
+            region.y2 += dy;
+        }
+        Side::Bottom => {
+            let dy = (height * inset_percent / 100).min(region.y1);
+            region.y1 -= dy;
+            region.y2 -= dy;
+        }
+        Side::Left => {
+            let dx = (width * inset_percent / 100).min(width - region.x2);
+            region.x1 += dx;
+            region.x2 += dx;
+        }
+        Side::Right => {
+            let dx = (width * inset_percent / 100).min(region.x1);
+            region.x1 -= dx;
+            region.x2 -= dx;
+        }
+    }
+    region
+}
+
+/// Регион стороны экрана с учётом минимального числа пикселей и отступа от края.
+fn edge_region(
+    region: LedRegion,
+    side: Side,
+    config: &AmbilightConfig,
+    width: usize,
+    height: usize,
+) -> LedRegion {
+    let inset_percent = match side {
+        Side::Top => config.inset_top,
+        Side::Bottom => config.inset_bottom,
+        Side::Left => config.inset_left,
+        Side::Right => config.inset_right,
+    };
+    let region = expand_to_min_pixels(region, side, config.min_pixels_per_region, config.sample_step.max(1), width, height);
+    LedRegion { edge: Some(side), ..inset_region(region, side, inset_percent, width, height) }
+}
+
+/// Угол экрана.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Регионы угловых светодиодов: пересечение регионов двух соседних сторон
+/// (`size_x` x `size_y` в углу экрана) делится на `count` полос, идущих в том же
+/// направлении, в котором лента приходит в угол.
+fn corner_regions(
+    corner: Corner,
+    count: usize,
+    (size_x, size_y): (usize, usize),
+    width: usize,
+    height: usize,
+) -> Vec<LedRegion> {
+    let (size_x, size_y) = (size_x.min(width), size_y.min(height));
+    let (x0, y0) = match corner {
+        Corner::TopLeft => (0, 0),
+        Corner::TopRight => (width - size_x, 0),
+        Corner::BottomLeft => (0, height - size_y),
+        Corner::BottomRight => (width - size_x, height - size_y),
+    };
+    let along = match corner {
+        Corner::BottomRight | Corner::TopLeft => size_x,
+        Corner::TopRight | Corner::BottomLeft => size_y,
+    };
+    let step = along as f32 / count.max(1) as f32;
+    (0..count)
+        .map(|i| {
+            let start = (i as f32 * step).round() as usize;
+            let end = ((i + 1) as f32 * step).round() as usize;
+            match corner {
+                // Снизу лента идёт слева направо
+                Corner::BottomRight => LedRegion { x1: x0 + start, y1: y0, x2: x0 + end, y2: y0 + size_y, edge: None },
+                // Справа снизу вверх
+                Corner::TopRight => {
+                    LedRegion { x1: x0, y1: y0 + size_y - end, x2: x0 + size_x, y2: y0 + size_y - start, edge: None }
+                }
+                // Сверху справа налево
+                Corner::TopLeft => {
+                    LedRegion { x1: x0 + size_x - end, y1: y0, x2: x0 + size_x - start, y2: y0 + size_y, edge: None }
+                }
+                // Слева сверху вниз
+                Corner::BottomLeft => LedRegion { x1: x0, y1: y0 + start, x2: x0 + size_x, y2: y0 + end, edge: None },
+            }
+        })
+        .collect()
+}
+
+/// Создаёт вектор регионов (LedRegion) в нужном порядке.
+pub fn create_led_regions(config: &AmbilightConfig, width: usize, height: usize) -> Vec<LedRegion> {
+    let mut regions = Vec::new();
+
+    let [top, bottom, left, right] =
+        [Side::Top, Side::Bottom, Side::Left, Side::Right].map(|side| config.side_thickness(side, width, height));
+
+    let total_bottom = config.bottom_left_led_count + config.bottom_right_led_count;
+    let offset_pixels = width * config.offset / 100;
+    let effective_width = width.saturating_sub(offset_pixels);
+    // Шаг светодиода внизу: общий для обеих групп. Без нижних светодиодов стороны
+    // и верх всё равно строятся
+    let ratio_pitch = if total_bottom > 0 { effective_width as f32 / total_bottom as f32 } else { 0.0 };
+    let pitch = match config.bottom_group_sizing {
+        BottomGroupSizing::Pitch if config.top_led_count > 0 => {
+            (width as f32 / config.top_led_count as f32).min(ratio_pitch)
+        }
+        _ => ratio_pitch,
+    };
+    let left_group_width = ((config.bottom_left_led_count as f32 * pitch).round() as usize).min(effective_width);
+    let right_group_width = match config.bottom_group_sizing {
+        // Остаток ширины целиком: при округлении половинок обеих групп вверх
+        // правая группа иначе вылезала бы за край экрана на пиксель
+        BottomGroupSizing::Ratio if config.bottom_right_led_count > 0 => effective_width - left_group_width,
+        _ => (config.bottom_right_led_count as f32 * pitch).round() as usize,
+    };
+    let right_group_start = match config.bottom_group_sizing {
+        BottomGroupSizing::Ratio => left_group_width + offset_pixels,
+        // Правая группа прижата к правому углу
+        BottomGroupSizing::Pitch => width.saturating_sub(right_group_width).max(left_group_width),
+    };
+    // Крайние у разрыва светодиоды захватывают часть разрыва, чтобы под подставкой
+    // монитора не было резкого перехода
+    let gap_pixels = right_group_start - left_group_width;
+    let gap_feather = gap_pixels * config.bottom_gap_feather.min(50) / 100;
+
+    // 1) Нижняя правая группа: слева → направо
+    if config.bottom_right_led_count > 0 {
+        let seg_w = right_group_width as f32 / config.bottom_right_led_count as f32;
+        for i in 0..config.bottom_right_led_count {
+            let mut x1 = (right_group_start as f32 + i as f32 * seg_w).round() as usize;
+            let x2 = (right_group_start as f32 + (i + 1) as f32 * seg_w).round() as usize;
+            if i == 0 {
+                x1 = x1.saturating_sub(gap_feather);
+            }
+            regions.push(edge_region(
+                LedRegion {
+                    x1: x1.min(width),
+                    y1: height.saturating_sub(bottom),
+                    x2: x2.min(width),
+                    y2: height,
+                    edge: None,
+                },
+                Side::Bottom,
+                config,
+                width,
+                height,
+            ));
+        }
+    }
+
+    // Угол снизу справа
+    regions.extend(corner_regions(
+        Corner::BottomRight,
+        config.bottom_right_corner_led_count,
+        (right, bottom),
+        width,
+        height,
+    ));
+
+    // 2) Правая сторона: снизу → вверх
+    if config.right_led_count > 0 {
+        let seg_h = height as f32 / config.right_led_count as f32;
+        for i in 0..config.right_led_count {
+            let y1 = (height as f32 - (i + 1) as f32 * seg_h).round() as usize;
+            let y2 = (height as f32 - i as f32 * seg_h).round() as usize;
+            regions.push(edge_region(
+                LedRegion {
+                    x1: width.saturating_sub(right),
+                    y1: y1.min(height),
+                    x2: width,
+                    y2: y2.min(height),
+                    edge: None,
+                },
+                Side::Right,
+                config,
+                width,
+                height,
+            ));
+        }
+    }
+
+    // Угол сверху справа
+    regions.extend(corner_regions(
+        Corner::TopRight,
+        config.top_right_corner_led_count,
+        (right, top),
+        width,
+        height,
+    ));
+
+    // 3) Верхняя сторона: справа → налево
+    if config.top_led_count > 0 {
+        let seg_w = width as f32 / config.top_led_count as f32;
+        for i in 0..config.top_led_count {
+            let rev_i = config.top_led_count - 1 - i;
+            let x1 = (rev_i as f32 * seg_w).round() as usize;
+            let x2 = ((rev_i + 1) as f32 * seg_w).round() as usize;
+            regions.push(edge_region(
+                LedRegion {
+                    x1: x1.min(width),
+                    y1: 0,
+                    x2: x2.min(width),
+                    y2: top,
+                    edge: None,
+                },
+                Side::Top,
+                config,
+                width,
+                height,
+            ));
+        }
+    }
+
+    // Угол сверху слева
+    regions.extend(corner_regions(
+        Corner::TopLeft,
+        config.top_left_corner_led_count,
+        (left, top),
+        width,
+        height,
+    ));
+
+    // 4) Левая сторона: сверху → вниз
+    if config.left_led_count > 0 {
+        let seg_h = height as f32 / config.left_led_count as f32;
+        for i in 0..config.left_led_count {
+            let y1 = (i as f32 * seg_h).round() as usize;
+            let y2 = ((i + 1) as f32 * seg_h).round() as usize;
+            regions.push(edge_region(
+                LedRegion {
+                    x1: 0,
+                    y1: y1.min(height),
+                    x2: left,
+                    y2: y2.min(height),
+                    edge: None,
+                },
+                Side::Left,
+                config,
+                width,
+                height,
+            ));
+        }
+    }
+
+    // Угол снизу слева
+    regions.extend(corner_regions(
+        Corner::BottomLeft,
+        config.bottom_left_corner_led_count,
+        (left, bottom),
+        width,
+        height,
+    ));
+
+    // 5) Нижняя левая группа: слева → направо
+    if config.bottom_left_led_count > 0 {
+        let seg_w = left_group_width as f32 / config.bottom_left_led_count as f32;
+        for i in 0..config.bottom_left_led_count {
+            let x1 = (i as f32 * seg_w).round() as usize;
+            let mut x2 = ((i + 1) as f32 * seg_w).round() as usize;
+            if i == config.bottom_left_led_count - 1 {
+                x2 += gap_feather;
+            }
+            regions.push(edge_region(
+                LedRegion {
+                    x1: x1.min(width),
+                    y1: height.saturating_sub(bottom),
+                    x2: x2.min(width),
+                    y2: height,
+                    edge: None,
+                },
+                Side::Bottom,
+                config,
+                width,
+                height,
+            ));
+        }
+    }
+
+    regions
+}
+
+/// Разбивает весь экран на сетку `columns` x `rows` и возвращает ячейки
+/// построчно сверху вниз в заданном порядке подключения.
+pub fn create_matrix_regions(
+    columns: usize,
+    rows: usize,
+    wiring: MatrixWiring,
+    width: usize,
+    height: usize,
+) -> Vec<LedRegion> {
+    let mut regions = Vec::with_capacity(columns * rows);
+    if columns == 0 || rows == 0 {
+        return regions;
+    }
+
+    let cell_w = width as f32 / columns as f32;
+    let cell_h = height as f32 / rows as f32;
+    for row in 0..rows {
+        let y1 = (row as f32 * cell_h).round() as usize;
+        let y2 = ((row + 1) as f32 * cell_h).round() as usize;
+        for i in 0..columns {
+            let column = match wiring {
+                MatrixWiring::Serpentine if row % 2 == 1 => columns - 1 - i,
+                _ => i,
+            };
+            let x1 = (column as f32 * cell_w).round() as usize;
+            let x2 = ((column + 1) as f32 * cell_w).round() as usize;
+            regions.push(LedRegion {
+                x1: x1.min(width),
+                y1: y1.min(height),
+                x2: x2.min(width),
+                y2: y2.min(height),
+                edge: None,
+            });
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(extra: &str) -> AmbilightConfig {
+        let base = r#"
+            fps = 60
+            port_name = "COM1"
+            baud_rate = 500000
+            top_led_count = 10
+            left_led_count = 5
+            right_led_count = 5
+            bottom_left_led_count = 4
+            bottom_right_led_count = 4
+            offset = 10
+            invert_direction = false
+            pixel_thickness = 1
+            brightness = 100
+            white_balance_temperature = 6600.0
+            gamma = 1.0
+        "#;
+        toml::from_str(&format!("{}\n{}", base, extra)).unwrap()
+    }
+
+    fn area(region: &LedRegion) -> usize {
+        (region.x2 - region.x1) * (region.y2 - region.y1)
+    }
+
+    #[test]
+    fn thin_regions_grow_to_min_pixels() {
+        // 1% от высоты 100 — полоса толщиной в 1 пиксель
+        let thin = create_led_regions(&test_config(""), 200, 100);
+        assert!(thin.iter().any(|region| area(region) < 100));
+
+        let grown = create_led_regions(&test_config("min_pixels_per_region = 100"), 200, 100);
+        assert_eq!(grown.len(), thin.len());
+        for region in &grown {
+            assert!(area(region) >= 100);
+            assert!(region.x2 <= 200 && region.y2 <= 100);
+        }
+        // Регион верхней стороны растёт вниз от края, а не сдвигается
+        let top = &grown[4 + 5];
+        assert_eq!(top.y1, 0);
+        assert_eq!(top.y2, 5);
+
+        // С шагом выборки 2 считаются только пиксели, которые попадут в среднее
+        let sparse = create_led_regions(&test_config("min_pixels_per_led = 100\nsample_step = 2"), 200, 100);
+        let top = &sparse[4 + 5];
+        let sampled = (top.x2 - top.x1).div_ceil(2) * (top.y2 - top.y1).div_ceil(2);
+        assert!(sampled >= 100);
+        assert_eq!(top.y2, 19);
+    }
+
+    #[test]
+    fn inset_moves_edge_regions_inward() {
+        let plain = create_led_regions(&test_config(""), 200, 100);
+        let inset = create_led_regions(&test_config("inset_top = 5\ninset_left = 10"), 200, 100);
+        assert_eq!(inset.len(), plain.len());
+
+        // Сначала 4 нижних правых и 5 правых регионов, затем 10 верхних и 5 левых
+        let (top, plain_top) = (&inset[9], &plain[9]);
+        assert_eq!((top.y1, top.y2), (plain_top.y1 + 5, plain_top.y2 + 5));
+        assert_eq!((top.x1, top.x2), (plain_top.x1, plain_top.x2));
+        let (left, plain_left) = (&inset[19], &plain[19]);
+        assert_eq!((left.x1, left.x2), (plain_left.x1 + 20, plain_left.x2 + 20));
+        // Стороны без отступа не меняются
+        assert_eq!((inset[0].y1, inset[0].y2), (plain[0].y1, plain[0].y2));
+    }
+
+    #[test]
+    fn bottom_groups_sized_by_ratio_or_pitch() {
+        let mut config = test_config("");
+        config.bottom_left_led_count = 2;
+        let bottom = |regions: &[LedRegion]| -> Vec<(usize, usize)> {
+            // Нижняя правая группа идёт первой, нижняя левая — последней
+            let mut spans: Vec<_> = regions[..4].iter().map(|r| (r.x1, r.x2)).collect();
+            spans.extend(regions[regions.len() - 2..].iter().map(|r| (r.x1, r.x2)));
+            spans
+        };
+
+        // 200 пикселей без 10% разрыва делятся на 6 светодиодов по 30 пикселей
+        assert_eq!(
+            bottom(&create_led_regions(&config, 200, 100)),
+            [(80, 110), (110, 140), (140, 170), (170, 200), (0, 30), (30, 60)]
+        );
+
+        // Шаг как у верхней стороны: 200 / 10 = 20 пикселей, разрыв 40..120
+        config.bottom_group_sizing = BottomGroupSizing::Pitch;
+        assert_eq!(
+            bottom(&create_led_regions(&config, 200, 100)),
+            [(120, 140), (140, 160), (160, 180), (180, 200), (0, 20), (20, 40)]
+        );
+    }
+
+    /// Проверяет, что отрезки идут друг за другом без щелей и наложений от `from` до `to`.
+    fn assert_tiles(spans: &[(usize, usize)], from: usize, to: usize) {
+        assert_eq!(spans.first().map(|span| span.0), Some(from), "{:?}", spans);
+        assert_eq!(spans.last().map(|span| span.1), Some(to), "{:?}", spans);
+        for pair in spans.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0, "{:?}", spans);
+        }
+        assert!(spans.iter().all(|&(start, end)| start < end), "{:?}", spans);
+    }
+
+    #[test]
+    fn regions_tile_each_edge_without_gaps() {
+        let counts = [
+            (10, 5, 5, 4, 4, 10),
+            (31, 17, 17, 7, 6, 13),
+            (58, 32, 32, 29, 29, 0),
+            (7, 3, 4, 1, 2, 25),
+            (1, 1, 1, 1, 1, 0),
+            (12, 6, 6, 0, 0, 10),
+        ];
+        let sizes = [(200, 100), (201, 101), (1366, 768), (1920, 1080), (2560, 1080), (3440, 1440), (1001, 333)];
+        for (top, left, right, bottom_left, bottom_right, offset) in counts {
+            for sizing in [BottomGroupSizing::Ratio, BottomGroupSizing::Pitch] {
+                for (width, height) in sizes {
+                    let mut config = test_config("");
+                    (config.top_led_count, config.left_led_count, config.right_led_count) = (top, left, right);
+                    (config.bottom_left_led_count, config.bottom_right_led_count) = (bottom_left, bottom_right);
+                    config.offset = offset;
+                    config.bottom_group_sizing = sizing;
+                    let regions = create_led_regions(&config, width, height);
+                    assert_eq!(regions.len(), top + left + right + bottom_left + bottom_right);
+                    assert!(regions.iter().all(|r| r.x2 <= width && r.y2 <= height));
+
+                    let (bottom_right_group, rest) = regions.split_at(bottom_right);
+                    let (right_side, rest) = rest.split_at(right);
+                    let (top_side, rest) = rest.split_at(top);
+                    let (left_side, bottom_left_group) = rest.split_at(left);
+                    let spans = |regions: &[LedRegion], vertical: bool| -> Vec<(usize, usize)> {
+                        regions.iter().map(|r| if vertical { (r.y1, r.y2) } else { (r.x1, r.x2) }).collect()
+                    };
+
+                    let reversed = |mut spans: Vec<(usize, usize)>| {
+                        spans.reverse();
+                        spans
+                    };
+                    assert_tiles(&reversed(spans(right_side, true)), 0, height);
+                    assert_tiles(&reversed(spans(top_side, false)), 0, width);
+                    assert_tiles(&spans(left_side, true), 0, height);
+
+                    // Низ: левая группа от левого края, правая — до правого, между ними разрыв
+                    if bottom_left + bottom_right == 0 {
+                        continue;
+                    }
+                    let left_group = spans(bottom_left_group, false);
+                    let right_group = spans(bottom_right_group, false);
+                    assert_tiles(&left_group, 0, left_group.last().unwrap().1);
+                    assert_tiles(&right_group, right_group[0].0, width);
+                    assert!(left_group.last().unwrap().1 <= right_group[0].0);
+                    if sizing == BottomGroupSizing::Ratio {
+                        let gap = right_group[0].0 - left_group.last().unwrap().1;
+                        assert_eq!(gap, width * offset / 100, "{}x{}", width, height);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_led_config_builds_no_regions() {
+        let mut config = test_config("");
+        config.top_led_count = 0;
+        config.left_led_count = 0;
+        config.right_led_count = 0;
+        config.bottom_left_led_count = 0;
+        config.bottom_right_led_count = 0;
+        assert!(create_led_regions(&config, 1920, 1080).is_empty());
+        // Нулевой экран тоже не приводит к делению на ноль
+        assert!(create_led_regions(&config, 0, 0).is_empty());
+        assert!(create_matrix_regions(0, 0, MatrixWiring::default(), 1920, 1080).is_empty());
+    }
+
+    #[test]
+    fn side_thickness_in_percent_of_its_dimension_or_pixels() {
+        let mut config = test_config("thickness_left = \"10px\"\nthickness_right = \"5%\"\nthickness_top = \"20%\"");
+        config.pixel_thickness = 3;
+        let regions = create_led_regions(&config, 200, 100);
+        let side = |side| regions.iter().find(|region| region.edge == Some(side)).unwrap();
+        assert_eq!(side(Side::Left).x2, 10);
+        // Левая и правая стороны — в процентах ширины, верх и низ — высоты
+        assert_eq!(side(Side::Right).x1, 190);
+        assert_eq!(side(Side::Top).y2, 20);
+        // Не заданная сторона — pixel_thickness процентов высоты, как раньше
+        assert_eq!(side(Side::Bottom).y1, 97);
+
+        assert_eq!(Thickness::try_from("40px".to_string()), Ok(Thickness::Pixels(40)));
+        assert!(Thickness::try_from("120%".to_string()).is_err());
+        assert!(Thickness::try_from("40".to_string()).is_err());
+    }
+
+    #[test]
+    fn corner_regions_follow_strip_order() {
+        let mut config = test_config("top_right_corner_led_count = 2\nbottom_left_corner_led_count = 1");
+        config.pixel_thickness = 10;
+        let regions = create_led_regions(&config, 200, 100);
+        assert_eq!(regions.len(), 4 + 5 + 2 + 10 + 5 + 1 + 4);
+
+        // После правой стороны (снизу вверх) — угол сверху справа, тоже снизу вверх
+        let top_right = &regions[4 + 5..4 + 5 + 2];
+        assert_eq!((top_right[0].x1, top_right[0].y1, top_right[0].x2, top_right[0].y2), (190, 5, 200, 10));
+        assert_eq!((top_right[1].x1, top_right[1].y1, top_right[1].x2, top_right[1].y2), (190, 0, 200, 5));
+
+        // Угол снизу слева — между левой стороной и нижней левой группой
+        let bottom_left = &regions[4 + 5 + 2 + 10 + 5];
+        assert_eq!((bottom_left.x1, bottom_left.y1, bottom_left.x2, bottom_left.y2), (0, 90, 10, 100));
+    }
+}
@@ -0,0 +1,50 @@
+//! Перестановка светодиодов для лент, спаянных не по порядку регионов.
+
+/// Проверяет, что `remap` — перестановка `0..led_count`. Пустая перестановка выключена.
+pub fn validate_remap(remap: &[usize], led_count: usize) -> Result<(), String> {
+    if remap.is_empty() {
+        return Ok(());
+    }
+    if remap.len() != led_count {
+        return Err(format!("led_remap: {} позиций, а светодиодов {}", remap.len(), led_count));
+    }
+    let mut seen = vec![false; led_count];
+    for &position in remap {
+        match seen.get_mut(position) {
+            None => return Err(format!("led_remap: позиция {} вне ленты из {} светодиодов", position, led_count)),
+            Some(true) => return Err(format!("led_remap: позиция {} встречается дважды", position)),
+            Some(seen) => *seen = true,
+        }
+    }
+    Ok(())
+}
+
+/// Раскладывает цвета по физическим позициям: цвет светодиода `i` уходит в `remap[i]`.
+pub fn apply_remap(colors: &[(u8, u8, u8)], remap: &[usize], out: &mut Vec<(u8, u8, u8)>) {
+    out.clear();
+    out.resize(colors.len(), (0, 0, 0));
+    for (&color, &position) in colors.iter().zip(remap) {
+        if let Some(slot) = out.get_mut(position) {
+            *slot = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_move_to_physical_positions() {
+        let remap = [2, 0, 1];
+        assert!(validate_remap(&remap, 3).is_ok());
+        let mut out = Vec::new();
+        apply_remap(&[(1, 0, 0), (2, 0, 0), (3, 0, 0)], &remap, &mut out);
+        assert_eq!(out, [(2, 0, 0), (3, 0, 0), (1, 0, 0)]);
+
+        assert!(validate_remap(&[], 3).is_ok());
+        assert!(validate_remap(&[0, 1], 3).is_err());
+        assert!(validate_remap(&[0, 1, 3], 3).is_err());
+        assert!(validate_remap(&[0, 1, 1], 3).is_err());
+    }
+}
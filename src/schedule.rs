@@ -0,0 +1,98 @@
+//! Расписание яркости по времени суток.
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: f32 = 24.0 * 60.0;
+
+/// Точка расписания: к моменту `time` («ЧЧ:ММ» по местному времени) яркость
+/// доходит до `brightness` процентов. Между точками яркость меняется плавно.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrightnessPoint {
+    pub time: String,
+    pub brightness: usize,
+}
+
+/// Минуты от полуночи для времени «ЧЧ:ММ».
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.trim().parse().ok()?, minutes.trim().parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Проверяет время и яркость каждой точки.
+pub fn validate_schedule(points: &[BrightnessPoint]) -> Result<(), String> {
+    for point in points {
+        if parse_time(&point.time).is_none() {
+            return Err(format!("brightness_schedule: время \"{}\" должно быть в виде ЧЧ:ММ", point.time));
+        }
+        if point.brightness > 100 {
+            return Err(format!("brightness_schedule: яркость в {} больше 100", point.time));
+        }
+    }
+    Ok(())
+}
+
+/// Множитель яркости в `minute` минут от полуночи: линейно между соседними точками,
+/// после последней точки суток — к первой точке следующих. Пустое расписание — 1.0.
+pub fn brightness_at(points: &[BrightnessPoint], minute: f32) -> f32 {
+    let mut sorted: Vec<(f32, f32)> = points
+        .iter()
+        .filter_map(|point| Some((parse_time(&point.time)? as f32, point.brightness as f32 / 100.0)))
+        .collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (Some(&first), Some(&last)) = (sorted.first(), sorted.last()) else {
+        return 1.0;
+    };
+    let previous = sorted.iter().rev().find(|point| point.0 <= minute).copied().unwrap_or(last);
+    let next = sorted.iter().find(|point| point.0 > minute).copied().unwrap_or(first);
+
+    let span = (next.0 - previous.0).rem_euclid(MINUTES_PER_DAY);
+    if span == 0.0 {
+        return previous.1;
+    }
+    let t = (minute - previous.0).rem_euclid(MINUTES_PER_DAY) / span;
+    previous.1 + (next.1 - previous.1) * t
+}
+
+/// Множитель яркости по расписанию для текущего местного времени.
+pub fn current_brightness(points: &[BrightnessPoint]) -> f32 {
+    if points.is_empty() {
+        return 1.0;
+    }
+    let now = chrono::Local::now();
+    let minute = now.hour() as f32 * 60.0 + now.minute() as f32 + now.second() as f32 / 60.0;
+    brightness_at(points, minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(time: &str, brightness: usize) -> BrightnessPoint {
+        BrightnessPoint { time: time.to_string(), brightness }
+    }
+
+    #[test]
+    fn day_and_night_blend_over_the_transition() {
+        // День 100 %, ночь 30 %, переходы по часу вечером и утром
+        let schedule = [point("21:00", 100), point("22:00", 30), point("07:00", 30), point("08:00", 100)];
+        let at = |hours: f32| brightness_at(&schedule, hours * 60.0);
+        assert_eq!(at(12.0), 1.0);
+        assert!((at(21.5) - 0.65).abs() < 1e-6);
+        // Ночной отрезок переходит через полночь
+        assert!((at(0.0) - 0.3).abs() < 1e-6);
+        assert!((at(3.0) - 0.3).abs() < 1e-6);
+        assert!((at(7.5) - 0.65).abs() < 1e-6);
+    }
+
+    #[test]
+    fn schedule_times_are_validated() {
+        assert_eq!(brightness_at(&[], 600.0), 1.0);
+        assert_eq!(brightness_at(&[point("23:30", 40)], 600.0), 0.4);
+        assert!(validate_schedule(&[point("7:05", 50)]).is_ok());
+        assert!(validate_schedule(&[point("24:00", 50)]).is_err());
+        assert!(validate_schedule(&[point("noon", 50)]).is_err());
+        assert!(validate_schedule(&[point("12:00", 150)]).is_err());
+    }
+}
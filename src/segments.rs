@@ -0,0 +1,133 @@
+use crate::color::color_temperature_to_rgb_multipliers;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Участок ленты (диапазон индексов светодиодов `start..end`) со своей цветокоррекцией,
+/// которая применяется поверх общей.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub gamma: Option<f32>,
+    pub white_balance_temperature: Option<f32>,
+    /// Яркость участка в процентах
+    pub brightness: Option<usize>,
+}
+
+/// Проверяет, что участки не пересекаются и вместе покрывают всю ленту.
+pub fn validate_segments(segments: &[Segment], led_count: usize) -> Result<(), String> {
+    if segments.is_empty() {
+        return Ok(());
+    }
+
+    let mut sorted: Vec<&Segment> = segments.iter().collect();
+    sorted.sort_by_key(|segment| segment.start);
+    let mut next = 0;
+    for segment in sorted {
+        if segment.start >= segment.end {
+            return Err(format!("Участок \"{}\": start должен быть меньше end", segment.name));
+        }
+        if segment.start < next {
+            return Err(format!("Участок \"{}\" пересекается с предыдущим", segment.name));
+        }
+        if segment.start > next {
+            return Err(format!("Светодиоды {}..{} не входят ни в один участок", next, segment.start));
+        }
+        next = segment.end;
+    }
+    if next != led_count {
+        return Err(format!(
+            "Участки покрывают {} светодиодов, а на ленте их {}",
+            next, led_count
+        ));
+    }
+    Ok(())
+}
+
+/// Предвычисленная коррекция одного участка.
+pub struct SegmentCorrection {
+    range: Range<usize>,
+    gamma: Option<f32>,
+    multipliers: (f32, f32, f32),
+}
+
+impl SegmentCorrection {
+    pub fn new(segment: &Segment) -> Self {
+        let (mut r, mut g, mut b) = segment
+            .white_balance_temperature
+            .map_or((1.0, 1.0, 1.0), color_temperature_to_rgb_multipliers);
+        if let Some(brightness) = segment.brightness {
+            let brightness = brightness as f32 / 100.0;
+            r *= brightness;
+            g *= brightness;
+            b *= brightness;
+        }
+        SegmentCorrection {
+            range: segment.start..segment.end,
+            gamma: segment.gamma,
+            multipliers: (r, g, b),
+        }
+    }
+
+    /// Применяет коррекцию к цветам своего участка.
+    pub fn apply(&self, colors: &mut [(u8, u8, u8)]) {
+        let Some(colors) = colors.get_mut(self.range.clone()) else {
+            return;
+        };
+        let correct = |c: u8, mult: f32| {
+            let mut value = c as f32;
+            if let Some(gamma) = self.gamma {
+                value = 255.0 * (value / 255.0).powf(gamma);
+            }
+            (value * mult).min(255.0) as u8
+        };
+        for (r, g, b) in colors {
+            *r = correct(*r, self.multipliers.0);
+            *g = correct(*g, self.multipliers.1);
+            *b = correct(*b, self.multipliers.2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: usize, end: usize) -> Segment {
+        Segment {
+            name: format!("{}..{}", start, end),
+            start,
+            end,
+            gamma: None,
+            white_balance_temperature: None,
+            brightness: None,
+        }
+    }
+
+    #[test]
+    fn segments_must_cover_the_strip_without_overlaps() {
+        // Порядок в настройках не важен
+        assert!(validate_segments(&[segment(60, 100), segment(0, 60)], 100).is_ok());
+        assert!(validate_segments(&[segment(0, 60), segment(50, 100)], 100).is_err());
+        assert!(validate_segments(&[segment(0, 50), segment(60, 100)], 100).is_err());
+        assert!(validate_segments(&[segment(0, 60), segment(60, 101)], 100).is_err());
+        assert!(validate_segments(&[segment(0, 60)], 100).is_err());
+        assert!(validate_segments(&[segment(0, 0), segment(0, 100)], 100).is_err());
+    }
+
+    #[test]
+    fn correction_changes_only_its_own_range() {
+        let correction = SegmentCorrection::new(&Segment { brightness: Some(50), ..segment(2, 4) });
+        let mut colors = vec![(200, 100, 50); 6];
+        correction.apply(&mut colors);
+        assert_eq!(colors[..2], [(200, 100, 50); 2]);
+        assert_eq!(colors[2..4], [(100, 50, 25); 2]);
+        assert_eq!(colors[4..], [(200, 100, 50); 2]);
+
+        let correction = SegmentCorrection::new(&Segment { gamma: Some(2.0), ..segment(0, 1) });
+        let mut colors = vec![(255, 128, 0), (128, 128, 128)];
+        correction.apply(&mut colors);
+        assert_eq!(colors, [(255, 64, 0), (128, 128, 128)]);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::config::AmbilightConfig;
+use crate::shutdown;
+use crate::strips::Outputs;
+use log::info;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Сколько горит каждая позиция бегущей точки.
+const CHASE_STEP: Duration = Duration::from_millis(40);
+/// Сколько держится каждая сплошная заливка.
+const FILL_DURATION: Duration = Duration::from_secs(1);
+
+/// Самопроверка ленты без захвата экрана: по ленте в порядке регионов бежит одна
+/// белая точка, затем лента по очереди заливается красным, зелёным и синим.
+/// Кадры идут тем же выводом и теми же пакетами, что и в обычной работе.
+pub fn run(config: &AmbilightConfig, led_count: usize) -> Result<(), Box<dyn Error>> {
+    let writer = Outputs::open(config, led_count)?;
+    let scale = |c: u8| (c as f32 * config.brightness.min(100) as f32 / 100.0) as u8;
+    let paint = |(r, g, b): (u8, u8, u8)| (scale(r), scale(g), scale(b));
+
+    info!(
+        "Самопроверка: {} светодиодов. Точка должна пройти по экрану в порядке регионов: \
+         от нижней правой группы вверх по правой стороне, по верху справа налево, \
+         вниз по левой стороне и по нижней левой группе",
+        led_count
+    );
+    for step in 0..led_count {
+        if shutdown::stop_requested() {
+            break;
+        }
+        // Регионы идут в обратном порядке, если лента подключена с другого конца
+        let index = if config.invert_direction { led_count - 1 - step } else { step };
+        let mut colors = vec![(0, 0, 0); led_count];
+        colors[index] = paint((255, 255, 255));
+        writer.send(colors);
+        thread::sleep(CHASE_STEP);
+    }
+
+    for (name, color) in [("красный", (255, 0, 0)), ("зелёный", (0, 255, 0)), ("синий", (0, 0, 255))] {
+        info!("Заливка: {}", name);
+        let started = Instant::now();
+        // Кадр повторяется, чтобы вывод с таймаутом (WLED) не вернулся к своему эффекту
+        while started.elapsed() < FILL_DURATION && !shutdown::stop_requested() {
+            writer.send(vec![paint(color); led_count]);
+            thread::sleep(CHASE_STEP);
+        }
+    }
+
+    writer.stop();
+    info!("Самопроверка завершена, лента погашена");
+    Ok(())
+}
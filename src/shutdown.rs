@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Выставляется по Ctrl-C, SIGTERM или команде остановки службы Windows.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Запрошена ли остановка программы.
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Просит главный цикл погасить ленту и завершиться.
+pub fn request_stop() {
+    STOP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    request_stop();
+}
+
+/// Подписывается на SIGINT (Ctrl-C) и SIGTERM, чтобы перед выходом погасить ленту.
+#[cfg(unix)]
+pub fn install_handler() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: обработчик только выставляет атомарный флаг
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_console_event(event: u32) -> windows_sys::Win32::Foundation::BOOL {
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    if event == CTRL_C_EVENT || event == CTRL_BREAK_EVENT {
+        request_stop();
+        1
+    } else {
+        0
+    }
+}
+
+/// Подписывается на Ctrl-C и Ctrl-Break в консоли, чтобы перед выходом погасить ленту.
+#[cfg(windows)]
+pub fn install_handler() {
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    // SAFETY: обработчик только выставляет атомарный флаг
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_console_event), 1);
+    }
+}
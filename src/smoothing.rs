@@ -0,0 +1,243 @@
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use std::time::Duration;
+
+/// Изменение канала между кадрами, при котором светодиод считается полностью «в движении».
+const MOTION_FULL_SCALE: f32 = 32.0;
+
+/// Сглаживание, зависящее от движения: светодиод, цвет которого почти не изменился,
+/// сглаживается сильно (`max`), а изменившийся на `motion_threshold` и больше — слабо (`min`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSmoothing {
+    pub min: f32,
+    pub max: f32,
+    pub motion_threshold: f32,
+}
+
+impl AdaptiveSmoothing {
+    /// Сила сглаживания для изменения канала на `motion`.
+    fn at(&self, motion: f32) -> f32 {
+        let t = (motion / self.motion_threshold).min(1.0);
+        self.max - (self.max - self.min) * t
+    }
+}
+
+/// Сила сглаживания за кадр длительностью `elapsed` для постоянной времени `time_constant`:
+/// за `time_constant` от прежнего цвета остаётся 1/e, с какой бы частотой ни шли кадры.
+pub fn smoothing_for_interval(time_constant: Duration, elapsed: Duration) -> f32 {
+    if time_constant.is_zero() {
+        return 0.0;
+    }
+    (-elapsed.as_secs_f32() / time_constant.as_secs_f32()).exp()
+}
+
+/// Экспоненциальное сглаживание цветов светодиодов между кадрами.
+/// Хранит последние отправленные цвета (в пространстве sRGB, 0.0–255.0).
+#[derive(Default)]
+pub struct Smoother {
+    previous: Vec<(f32, f32, f32)>,
+}
+
+impl Smoother {
+    /// Смешивает новые цвета с предыдущими: `new = alpha * current + (1 - alpha) * previous`,
+    /// где `alpha = 1 - smoothing`. При `linear` смешивание идёт в линейном свете.
+    pub fn apply(&mut self, colors: &mut [(u8, u8, u8)], smoothing: f32, linear: bool) {
+        self.apply_with(colors, linear, smoothing <= 0.0, |_| smoothing);
+    }
+
+    /// Как [`Smoother::apply`], но сила сглаживания своя у каждого светодиода
+    /// и зависит от того, насколько изменился его цвет с прошлого кадра.
+    pub fn apply_adaptive(&mut self, colors: &mut [(u8, u8, u8)], adaptive: AdaptiveSmoothing, linear: bool) {
+        self.apply_with(colors, linear, adaptive.max <= 0.0, |motion| adaptive.at(motion));
+    }
+
+    /// `smoothing` получает наибольшее изменение канала светодиода с прошлого кадра.
+    fn apply_with(
+        &mut self,
+        colors: &mut [(u8, u8, u8)],
+        linear: bool,
+        disabled: bool,
+        smoothing: impl Fn(f32) -> f32,
+    ) {
+        // Без сглаживания или при смене числа светодиодов просто запоминаем кадр
+        if disabled || self.previous.len() != colors.len() {
+            self.previous = colors.iter().map(|&(r, g, b)| (r as f32, g as f32, b as f32)).collect();
+            return;
+        }
+
+        for (color, previous) in colors.iter_mut().zip(&mut self.previous) {
+            let motion = (color.0 as f32 - previous.0)
+                .abs()
+                .max((color.1 as f32 - previous.1).abs())
+                .max((color.2 as f32 - previous.2).abs());
+            let alpha = 1.0 - smoothing(motion).clamp(0.0, 1.0);
+            *previous = (
+                blend(color.0, previous.0, alpha, linear),
+                blend(color.1, previous.1, alpha, linear),
+                blend(color.2, previous.2, alpha, linear),
+            );
+            *color = (
+                previous.0.round() as u8,
+                previous.1.round() as u8,
+                previous.2.round() as u8,
+            );
+        }
+    }
+
+    /// Сглаживает ступеньки между соседними светодиодами там, где цвет меняется
+    /// от кадра к кадру (движущийся градиент), не трогая статичные участки.
+    /// При `strength = 1` и сильном движении светодиод усредняется с двумя соседями поровну.
+    pub fn antialias(&self, colors: &mut [(u8, u8, u8)], strength: f32) {
+        if strength <= 0.0 || self.previous.len() != colors.len() || colors.len() < 3 {
+            return;
+        }
+
+        let current = colors.to_vec();
+        for (i, color) in colors.iter_mut().enumerate() {
+            let (r, g, b) = current[i];
+            let previous = self.previous[i];
+            let motion = (r as f32 - previous.0)
+                .abs()
+                .max((g as f32 - previous.1).abs())
+                .max((b as f32 - previous.2).abs());
+            let weight = strength.min(1.0) * (motion / MOTION_FULL_SCALE).min(1.0) * 2.0 / 3.0;
+            if weight == 0.0 {
+                continue;
+            }
+
+            let left = current[i.saturating_sub(1)];
+            let right = current[(i + 1).min(current.len() - 1)];
+            let mix = |c: u8, l: u8, r: u8| {
+                ((1.0 - weight) * c as f32 + weight * (l as f32 + r as f32) / 2.0).round() as u8
+            };
+            *color = (mix(r, left.0, right.0), mix(g, left.1, right.1), mix(b, left.2, right.2));
+        }
+    }
+}
+
+/// Мёртвая зона по каждому каналу: канал светодиода обновляется, только если
+/// отличается от последнего выведенного значения больше чем на `threshold`.
+/// Убирает мерцание ±1 из-за шума усреднения, заметное на малой яркости.
+#[derive(Default)]
+pub struct Deadband {
+    last: Vec<(u8, u8, u8)>,
+}
+
+impl Deadband {
+    pub fn apply(&mut self, colors: &mut [(u8, u8, u8)], threshold: u8) {
+        if threshold == 0 || self.last.len() != colors.len() {
+            self.last = colors.to_vec();
+            return;
+        }
+
+        let hold = |current: u8, last: &mut u8| {
+            if current.abs_diff(*last) > threshold {
+                *last = current;
+            }
+            *last
+        };
+        for (color, last) in colors.iter_mut().zip(&mut self.last) {
+            *color = (hold(color.0, &mut last.0), hold(color.1, &mut last.1), hold(color.2, &mut last.2));
+        }
+    }
+}
+
+/// Промежуточный кадр между `from` и `to`: `t = 0` — `from`, `t = 1` — `to`.
+/// Кадры разной длины не смешиваются, берётся `to`.
+pub fn interpolate(from: &[(u8, u8, u8)], to: &[(u8, u8, u8)], t: f32, linear: bool) -> Vec<(u8, u8, u8)> {
+    let t = t.clamp(0.0, 1.0);
+    if from.len() != to.len() || t == 1.0 {
+        return to.to_vec();
+    }
+    let mix = |to: u8, from: u8| blend(to, from as f32, t, linear).round() as u8;
+    from.iter().zip(to).map(|(&(r1, g1, b1), &(r2, g2, b2))| (mix(r2, r1), mix(g2, g1), mix(b2, b1))).collect()
+}
+
+fn blend(current: u8, previous: f32, alpha: f32, linear: bool) -> f32 {
+    if linear {
+        let current = srgb_to_linear(current as f32 / 255.0);
+        let previous = srgb_to_linear(previous / 255.0);
+        255.0 * linear_to_srgb(alpha * current + (1.0 - alpha) * previous)
+    } else {
+        alpha * current as f32 + (1.0 - alpha) * previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_blend_is_brighter_than_srgb_blend() {
+        // Середина между чёрным и белым: в sRGB это 127.5, в линейном свете ~188
+        let srgb = blend(255, 0.0, 0.5, false);
+        let linear = blend(255, 0.0, 0.5, true);
+        assert!((srgb - 127.5).abs() < 0.01);
+        assert!((linear - 187.5).abs() < 1.0);
+    }
+
+    #[test]
+    fn first_frame_and_resize_pass_through() {
+        let mut smoother = Smoother::default();
+        let mut colors = vec![(200, 100, 0); 3];
+        smoother.apply(&mut colors, 0.5, true);
+        assert_eq!(colors, vec![(200, 100, 0); 3]);
+
+        let mut colors = vec![(0, 0, 0); 4];
+        smoother.apply(&mut colors, 0.5, true);
+        assert_eq!(colors, vec![(0, 0, 0); 4]);
+    }
+
+    #[test]
+    fn adaptive_smoothing_snaps_on_large_changes() {
+        let adaptive = AdaptiveSmoothing { min: 0.0, max: 0.9, motion_threshold: 32.0 };
+        let mut smoother = Smoother::default();
+        smoother.apply_adaptive(&mut [(100, 100, 100)], adaptive, false);
+
+        // Шум в 4 единицы сглаживается сильно и почти не доходит до ленты
+        let mut colors = [(104, 100, 100)];
+        smoother.apply_adaptive(&mut colors, adaptive, false);
+        assert_eq!(colors, [(101, 100, 100)]);
+
+        // Резкая смена сцены проходит сразу
+        let mut colors = [(10, 200, 100)];
+        smoother.apply_adaptive(&mut colors, adaptive, false);
+        assert_eq!(colors, [(10, 200, 100)]);
+    }
+
+    #[test]
+    fn time_constant_smoothing_does_not_depend_on_fps() {
+        let time_constant = Duration::from_millis(200);
+        // Один кадр по 1/30 с и два кадра по 1/60 с оставляют одинаковую долю прежнего цвета
+        let at_30 = smoothing_for_interval(time_constant, Duration::from_secs_f32(1.0 / 30.0));
+        let at_60 = smoothing_for_interval(time_constant, Duration::from_secs_f32(1.0 / 60.0));
+        assert!((at_30 - at_60 * at_60).abs() < 1e-6);
+        assert_eq!(smoothing_for_interval(Duration::ZERO, Duration::from_millis(16)), 0.0);
+    }
+
+    #[test]
+    fn deadband_suppresses_one_lsb_oscillation() {
+        let mut deadband = Deadband::default();
+        let mut first = [(10, 20, 30)];
+        deadband.apply(&mut first, 1);
+
+        for frame in [(11, 19, 31), (10, 20, 30), (9, 21, 29)] {
+            let mut colors = [frame];
+            deadband.apply(&mut colors, 1);
+            assert_eq!(colors, [(10, 20, 30)]);
+        }
+
+        // Настоящее изменение проходит сразу и только в изменившемся канале
+        let mut colors = [(15, 21, 30)];
+        deadband.apply(&mut colors, 1);
+        assert_eq!(colors, [(15, 20, 30)]);
+    }
+
+    #[test]
+    fn interpolation_moves_from_previous_to_current_frame() {
+        let (from, to) = ([(0, 100, 200)], [(100, 100, 0)]);
+        assert_eq!(interpolate(&from, &to, 0.0, false), from);
+        assert_eq!(interpolate(&from, &to, 0.25, false), [(25, 100, 150)]);
+        assert_eq!(interpolate(&from, &to, 2.0, false), to);
+        assert_eq!(interpolate(&[], &to, 0.5, false), to);
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+/// Внутреннее состояние, которое показывает статусный светодиод.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineStatus {
+    /// Экран не захватывается (статичный цвет или лента выключена) — медленная пульсация
+    Idle,
+    /// Идёт захват экрана — горит постоянно
+    Active,
+    /// Последняя отправка в порт не удалась — мигает
+    SerialError,
+}
+
+/// Цвета статусного светодиода для каждого состояния.
+pub struct StatusColors {
+    pub idle: (u8, u8, u8),
+    pub active: (u8, u8, u8),
+    pub error: (u8, u8, u8),
+}
+
+/// Период пульсации в режиме ожидания.
+const PULSE_PERIOD: Duration = Duration::from_secs(2);
+/// Полупериод мигания при ошибке.
+const BLINK_HALF_PERIOD: Duration = Duration::from_millis(250);
+
+/// Цвет статусного светодиода в момент `elapsed` от запуска программы.
+pub fn status_color(status: EngineStatus, elapsed: Duration, colors: &StatusColors) -> (u8, u8, u8) {
+    let scale = |(r, g, b): (u8, u8, u8), k: f32| {
+        ((r as f32 * k) as u8, (g as f32 * k) as u8, (b as f32 * k) as u8)
+    };
+    match status {
+        EngineStatus::Active => colors.active,
+        EngineStatus::Idle => {
+            let phase = elapsed.as_secs_f32() / PULSE_PERIOD.as_secs_f32() * std::f32::consts::TAU;
+            scale(colors.idle, (1.0 - phase.cos()) / 2.0)
+        }
+        EngineStatus::SerialError => {
+            let on = (elapsed.as_millis() / BLINK_HALF_PERIOD.as_millis()).is_multiple_of(2);
+            if on { colors.error } else { (0, 0, 0) }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use image::ExtendedColorType;
+use image::codecs::jpeg::JpegEncoder;
+use log::{info, warn};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Размер клетки одного светодиода на картинке, пиксели.
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 48;
+const JPEG_QUALITY: u8 = 85;
+
+/// Последние выведенные цвета ленты, которые показывает поток.
+pub type SharedColors = Arc<Mutex<Vec<(u8, u8, u8)>>>;
+
+/// Рисует ленту полосой: светодиоды слева направо в порядке подключения.
+fn render_strip(colors: &[(u8, u8, u8)], jpeg: &mut Vec<u8>) -> image::ImageResult<()> {
+    let width = colors.len().max(1) * CELL_WIDTH;
+    let mut pixels = Vec::with_capacity(width * CELL_HEIGHT * 3);
+    for _ in 0..CELL_HEIGHT {
+        for &(r, g, b) in colors {
+            for _ in 0..CELL_WIDTH {
+                pixels.extend_from_slice(&[r, g, b]);
+            }
+        }
+    }
+    pixels.resize(width * CELL_HEIGHT * 3, 0);
+
+    jpeg.clear();
+    JpegEncoder::new_with_quality(&mut *jpeg, JPEG_QUALITY).encode(
+        &pixels,
+        width as u32,
+        CELL_HEIGHT as u32,
+        ExtendedColorType::Rgb8,
+    )
+}
+
+fn handle_client(mut stream: TcpStream, colors: SharedColors, frame_duration: Duration) -> io::Result<()> {
+    // Запрос не разбираем: на любой путь отдаём поток, заголовки просто дочитываем
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" {
+        line.clear();
+    }
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=frame\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut jpeg = Vec::new();
+    loop {
+        let frame_start = Instant::now();
+        let snapshot = colors.lock().unwrap().clone();
+        render_strip(&snapshot, &mut jpeg).map_err(io::Error::other)?;
+        write!(stream, "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", jpeg.len())?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_duration {
+            thread::sleep(frame_duration - elapsed);
+        }
+    }
+}
+
+/// Запускает HTTP-сервер с MJPEG-потоком цветов ленты; его можно открыть в браузере
+/// или вставить на страницу через `<img src="http://host:port/">`. Слушает адрес `bind`.
+pub fn spawn_server(bind: &str, port: u16, fps: u32, colors: SharedColors) -> io::Result<()> {
+    let listener = TcpListener::bind((bind, port))?;
+    let frame_duration = Duration::from_millis(1000 / fps.max(1) as u64);
+    info!("MJPEG-поток доступен на {}:{}", bind, port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let colors = Arc::clone(&colors);
+                    thread::spawn(move || {
+                        // Закрытие вкладки браузера обрывает соединение — это не ошибка
+                        let _ = handle_client(stream, colors, frame_duration);
+                    });
+                }
+                Err(e) => warn!("Ошибка подключения к MJPEG-потоку: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
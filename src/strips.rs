@@ -0,0 +1,186 @@
+use crate::config::AmbilightConfig;
+use crate::segments::{Segment, SegmentCorrection};
+use crate::writer::{self, Writer};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::atomic::Ordering;
+
+/// Отдельный контроллер со своим портом (`[[strips]]`): получает светодиоды `start..end`
+/// общей раскладки, пересчитанные с начала своей ленты.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StripConfig {
+    pub port_name: String,
+    /// По умолчанию — общий `baud_rate`
+    pub baud_rate: Option<u32>,
+    pub start: usize,
+    pub end: usize,
+    pub gamma: Option<f32>,
+    pub white_balance_temperature: Option<f32>,
+    /// Яркость ленты в процентах
+    pub brightness: Option<usize>,
+}
+
+impl StripConfig {
+    /// Общие настройки с портом этого контроллера.
+    pub fn apply_to(&self, config: &AmbilightConfig) -> AmbilightConfig {
+        let mut config = config.clone();
+        config.port_name = self.port_name.clone();
+        config.baud_rate = self.baud_rate.unwrap_or(config.baud_rate);
+        config.strips.clear();
+        config
+    }
+
+    /// Цветокоррекция ленты как у участка, который занимает её целиком.
+    fn correction(&self) -> SegmentCorrection {
+        SegmentCorrection::new(&Segment {
+            name: self.port_name.clone(),
+            start: 0,
+            end: self.end - self.start,
+            gamma: self.gamma,
+            white_balance_temperature: self.white_balance_temperature,
+            brightness: self.brightness,
+        })
+    }
+}
+
+/// Проверяет, что ленты не пустые и не выходят за общую раскладку. Пересекаться
+/// они могут: один и тот же участок можно вывести на два контроллера.
+pub fn validate_strips(strips: &[StripConfig], led_count: usize) -> Result<(), String> {
+    for strip in strips {
+        if strip.start >= strip.end {
+            return Err(format!("Лента на {}: start должен быть меньше end", strip.port_name));
+        }
+        if strip.end > led_count {
+            return Err(format!(
+                "Лента на {} заканчивается на {}, а светодиодов всего {}",
+                strip.port_name, strip.end, led_count
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Одна лента с общим портом или по потоку вывода на каждую из `[[strips]]`.
+/// Цвета считаются один раз, каждому контроллеру уходит его часть.
+pub struct Outputs {
+    /// Пусто — весь кадр уходит в единственный `writers[0]`
+    strips: Vec<StripConfig>,
+    corrections: Vec<SegmentCorrection>,
+    writers: Vec<Writer>,
+}
+
+impl Outputs {
+    /// Открывает порты и запускает потоки вывода. Ошибка открытия уже выведена в журнал.
+    pub fn open(config: &AmbilightConfig, led_count: usize) -> io::Result<Self> {
+        let mut writers = Vec::new();
+        if config.strips.is_empty() {
+            let port = writer::open_output(config, led_count).inspect_err(|e| writer::report_open_error(config, e))?;
+            writers.push(Writer::spawn(config, port, led_count)?);
+        }
+        for strip in &config.strips {
+            let strip_config = strip.apply_to(config);
+            let strip_led_count = strip.end - strip.start;
+            let port = writer::open_output(&strip_config, strip_led_count)
+                .inspect_err(|e| writer::report_open_error(&strip_config, e))?;
+            info!("Лента {}: светодиоды {}..{}", strip.port_name, strip.start, strip.end);
+            writers.push(Writer::spawn(&strip_config, port, strip_led_count)?);
+        }
+        Ok(Outputs {
+            strips: config.strips.clone(),
+            corrections: config.strips.iter().map(StripConfig::correction).collect(),
+            writers,
+        })
+    }
+
+    /// Можно ли применить настройки без перезапуска: потоки вывода не добавляются и не убираются.
+    pub fn check_reload(&self, config: &AmbilightConfig) -> Result<(), String> {
+        if config.strips.len() != self.strips.len() {
+            return Err("Число [[strips]] меняется только перезапуском".to_string());
+        }
+        Ok(())
+    }
+
+    /// Передаёт перечитанные настройки потокам вывода; число лент уже сверено [`Outputs::check_reload`].
+    pub fn reconfigure(&mut self, config: &AmbilightConfig) {
+        if config.strips.is_empty() {
+            self.writers[0].reconfigure(config);
+        }
+        for (strip, writer) in config.strips.iter().zip(&self.writers) {
+            writer.reconfigure(&strip.apply_to(config));
+        }
+        self.strips.clone_from(&config.strips);
+        self.corrections = config.strips.iter().map(StripConfig::correction).collect();
+    }
+
+    /// Передаёт кадр потокам вывода, каждой ленте — её светодиоды.
+    pub fn send(&self, colors: Vec<(u8, u8, u8)>) {
+        if self.strips.is_empty() {
+            self.writers[0].send(colors);
+            return;
+        }
+        for ((strip, correction), writer) in self.strips.iter().zip(&self.corrections).zip(&self.writers) {
+            let Some(part) = colors.get(strip.start..strip.end) else {
+                continue;
+            };
+            let mut part = part.to_vec();
+            correction.apply(&mut part);
+            writer.send(part);
+        }
+    }
+
+    /// Гасит все ленты, не останавливая потоки вывода.
+    pub fn blank(&self) {
+        for writer in &self.writers {
+            writer.blank();
+        }
+    }
+
+    /// Кадров, ушедших на самую медленную ленту с прошлого вызова.
+    pub fn take_sent(&self) -> usize {
+        self.writers.iter().map(|writer| writer.stats.sent.swap(0, Ordering::Relaxed)).min().unwrap_or(0)
+    }
+
+    /// Кадров, отброшенных всеми потоками вывода с прошлого вызова.
+    pub fn take_dropped(&self) -> usize {
+        self.writers.iter().map(|writer| writer.stats.dropped.swap(0, Ordering::Relaxed)).sum()
+    }
+
+    /// Последняя отправка хотя бы на одну ленту закончилась ошибкой.
+    pub fn error(&self) -> bool {
+        self.writers.iter().any(|writer| writer.stats.error.load(Ordering::Relaxed))
+    }
+
+    /// Дожидается отправки последних кадров и гасит все ленты.
+    pub fn stop(self) {
+        for writer in self.writers {
+            writer.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(start: usize, end: usize) -> StripConfig {
+        StripConfig {
+            port_name: "COM4".to_string(),
+            baud_rate: None,
+            start,
+            end,
+            gamma: None,
+            white_balance_temperature: None,
+            brightness: None,
+        }
+    }
+
+    #[test]
+    fn strips_must_fit_the_layout() {
+        assert!(validate_strips(&[strip(0, 60), strip(60, 100)], 100).is_ok());
+        // Один участок на двух контроллерах
+        assert!(validate_strips(&[strip(0, 60), strip(0, 60)], 100).is_ok());
+        assert!(validate_strips(&[strip(60, 60)], 100).is_err());
+        assert!(validate_strips(&[strip(60, 101)], 100).is_err());
+    }
+}
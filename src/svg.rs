@@ -0,0 +1,77 @@
+use crate::regions::LedRegion;
+use std::fmt::Write as _;
+
+/// Сохраняет схему раскладки в SVG: контур экрана, регионы с номерами светодиодов,
+/// подписи сторон и стрелки порядка подключения от светодиода к светодиоду.
+pub fn write_layout_svg(
+    path: &str,
+    regions: &[LedRegion],
+    width: usize,
+    height: usize,
+) -> std::io::Result<()> {
+    let font_size = (height / 60).max(12);
+    let mut svg = String::new();
+    // write! в String не может завершиться ошибкой
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{width}" height="{height}" font-family="sans-serif">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="black"/></marker></defs>"#
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect x="0" y="0" width="{width}" height="{height}" fill="#f4f4f4" stroke="black" stroke-width="2"/>"##
+    );
+
+    // Подписи сторон экрана
+    let labels = [
+        (width / 2, height / 2 - height / 4, "Верх"),
+        (width / 2, height / 2 + height / 4, "Низ"),
+        (width / 4, height / 2, "Лево"),
+        (width * 3 / 4, height / 2, "Право"),
+    ];
+    for (x, y, label) in labels {
+        let _ = writeln!(
+            svg,
+            r##"<text x="{x}" y="{y}" font-size="{}" text-anchor="middle" fill="#888">{label}</text>"##,
+            font_size * 2
+        );
+    }
+
+    // Регионы: оттенок меняется по порядку светодиодов
+    for (index, region) in regions.iter().enumerate() {
+        let hue = index * 360 / regions.len().max(1);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="hsl({hue}, 80%, 60%)" fill-opacity="0.6" stroke="black" stroke-width="1"/>"#,
+            region.x1,
+            region.y1,
+            region.x2 - region.x1,
+            region.y2 - region.y1
+        );
+        let (cx, cy) = center(region);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{cx}" y="{cy}" font-size="{font_size}" text-anchor="middle" dominant-baseline="middle">{index}</text>"#
+        );
+    }
+
+    // Порядок подключения
+    for pair in regions.windows(2) {
+        let (x1, y1) = center(&pair[0]);
+        let (x2, y2) = center(&pair[1]);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="black" stroke-width="1" marker-end="url(#arrow)"/>"#
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    std::fs::write(path, svg)
+}
+
+fn center(region: &LedRegion) -> (usize, usize) {
+    ((region.x1 + region.x2) / 2, (region.y1 + region.y2) / 2)
+}
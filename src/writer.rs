@@ -0,0 +1,619 @@
+use crate::config::AmbilightConfig;
+use crate::ddp::DdpUdp;
+use crate::dmx::{self, DmxProtocol, DmxUdp};
+use crate::output::{self, OutputMode, WledUdp};
+use crate::packet::{self, ChannelLayout, DeltaEncoder};
+use crate::rate_limit::TokenBucket;
+use crate::remap;
+use crate::smoothing;
+use log::{debug, error, info, warn};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Пауза между попытками переподключиться к пропавшему порту.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Сколько ждать приветствие прошивки при `verify_led_count`: Arduino после открытия
+/// порта перезагружается около секунды.
+const FIRMWARE_BANNER_WAIT: Duration = Duration::from_secs(2);
+/// Как часто повторять неизменный кадр при `skip_unchanged_writes`: прошивки
+/// с таймаутом гасят ленту, если данные долго не приходят.
+const UNCHANGED_RESEND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Открывает последовательный порт для Arduino.
+fn open_port(config: &AmbilightConfig) -> serialport::Result<Box<dyn serialport::SerialPort>> {
+    serialport::new(&config.port_name, config.baud_rate)
+        .timeout(Duration::from_millis(config.serial_timeout_ms))
+        .open()
+}
+
+/// Число светодиодов из приветствия прошивки: первое число в первой полной строке,
+/// где оно есть (например, «Ada 120» или «LEDs: 120»).
+fn banner_led_count(banner: &[u8]) -> Option<usize> {
+    let complete = &banner[..banner.iter().rposition(|&byte| byte == b'\n')?];
+    String::from_utf8_lossy(complete).lines().find_map(|line| {
+        line.split(|c: char| !c.is_ascii_digit()).find(|digits| !digits.is_empty())?.parse().ok()
+    })
+}
+
+/// Ждёт приветствие прошивки (после открытия порта Arduino перезагружается и печатает его)
+/// и предупреждает, если число светодиодов в нём не совпадает с `led_count`.
+fn verify_firmware_led_count(port: &mut dyn Read, led_count: usize) {
+    let deadline = Instant::now() + FIRMWARE_BANNER_WAIT;
+    let mut banner = Vec::new();
+    let mut chunk = [0u8; 64];
+    let reported = loop {
+        if let Some(count) = banner_led_count(&banner) {
+            break Some(count);
+        }
+        if Instant::now() >= deadline {
+            break None;
+        }
+        match port.read(&mut chunk) {
+            Ok(read) => banner.extend_from_slice(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                warn!("Не удалось прочитать приветствие прошивки: {}", e);
+                return;
+            }
+        }
+    };
+    match reported {
+        Some(count) if count == led_count => info!("Прошивка подтверждает {} светодиодов", count),
+        Some(count) => error!(
+            "Прошивка сообщает о {} светодиодах, а в настройках их {}: проверьте NUM_LEDS в скетче и *_led_count",
+            count, led_count
+        ),
+        None => warn!(
+            "Прошивка не сообщила число светодиодов за {:?}; проверка verify_led_count пропущена",
+            FIRMWARE_BANNER_WAIT
+        ),
+    }
+}
+
+/// Открывает вывод, выбранный в `[output]`. `led_count` — светодиодов на ленте,
+/// для сверки с прошивкой при `verify_led_count`.
+pub fn open_output(config: &AmbilightConfig, led_count: usize) -> io::Result<Box<dyn Write + Send>> {
+    match config.output.mode {
+        OutputMode::Serial => {
+            let mut port = open_port(config)?;
+            if config.verify_led_count {
+                verify_firmware_led_count(&mut port, led_count);
+            }
+            Ok(port)
+        }
+        OutputMode::WledUdp => Ok(Box::new(WledUdp::connect(&config.output)?)),
+        OutputMode::ArtNet => Ok(Box::new(DmxUdp::connect(
+            DmxProtocol::ArtNet,
+            &config.output.dmx_address,
+            config.output.dmx_port.unwrap_or(dmx::ARTNET_PORT),
+            config.output.dmx_universe.unwrap_or(0),
+        )?)),
+        OutputMode::Sacn => Ok(Box::new(DmxUdp::connect(
+            DmxProtocol::Sacn,
+            &config.output.dmx_address,
+            config.output.dmx_port.unwrap_or(dmx::SACN_PORT),
+            config.output.dmx_universe.unwrap_or(1),
+        )?)),
+        OutputMode::Ddp => Ok(Box::new(DdpUdp::connect(&config.output.ddp_address, config.output.ddp_port)?)),
+    }
+}
+
+/// Объясняет, почему не открылся вывод: для последовательного порта перечисляет
+/// доступные порты и подсказывает нужный, если он единственный.
+pub fn report_open_error(config: &AmbilightConfig, error: &io::Error) {
+    if config.output.mode != OutputMode::Serial {
+        error!("Не удалось открыть вывод {:?}: {}", config.output.mode, error);
+        return;
+    }
+    error!("Не удалось открыть порт {}: {}", config.port_name, error);
+    match serialport::available_ports() {
+        Ok(ports) if ports.is_empty() => error!("Последовательные порты не найдены. Подключён ли контроллер?"),
+        Ok(ports) => {
+            let names: Vec<&str> = ports.iter().map(|port| port.port_name.as_str()).collect();
+            error!("Доступные порты: {}", names.join(", "));
+            if let [name] = names[..]
+                && name != config.port_name
+            {
+                error!("Возможно, нужен port_name = \"{}\"?", name);
+            } else if names.contains(&config.port_name.as_str()) {
+                error!("Порт {} есть, но, вероятно, занят другой программой", config.port_name);
+            }
+        }
+        Err(e) => error!("Не удалось получить список портов: {}", e),
+    }
+}
+
+/// Формирует пакет для выбранного вывода. `layout` действует только на последовательный порт.
+fn build_packet(
+    config: &AmbilightConfig,
+    colors: &[(u8, u8, u8)],
+    layout: ChannelLayout,
+    delta_encoder: &DeltaEncoder,
+    buf: &mut Vec<u8>,
+) {
+    match config.output.mode {
+        OutputMode::WledUdp => output::build_drgb_packet(colors, config.output.wled_timeout, buf),
+        // Для DMX и DDP — просто тройки R, G, B, по пакетам их раскладывают DmxUdp и DdpUdp
+        OutputMode::ArtNet | OutputMode::Sacn | OutputMode::Ddp => {
+            buf.clear();
+            buf.extend(colors.iter().flat_map(|&(r, g, b)| [r, g, b]));
+        }
+        OutputMode::Serial if config.delta_packets => delta_encoder.encode(colors, config.delta_threshold, buf),
+        OutputMode::Serial => packet::build_adalight_packet_with(colors, layout, buf),
+    }
+}
+
+/// Отличается ли каждый канал цветов не больше чем на `tolerance`.
+fn colors_match(a: &[(u8, u8, u8)], b: &[(u8, u8, u8)], tolerance: u8) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(&(r1, g1, b1), &(r2, g2, b2))| {
+            r1.abs_diff(r2) <= tolerance && g1.abs_diff(g2) <= tolerance && b1.abs_diff(b2) <= tolerance
+        })
+}
+
+/// Меняются ли настройки, от которых зависит открытый вывод.
+fn output_changed(old: &AmbilightConfig, new: &AmbilightConfig) -> bool {
+    (&old.port_name, old.baud_rate, old.serial_timeout_ms, &old.output)
+        != (&new.port_name, new.baud_rate, new.serial_timeout_ms, &new.output)
+}
+
+/// Два последних кадра главного цикла для промежуточных кадров при `output_fps`.
+struct Interpolator {
+    previous: Vec<(u8, u8, u8)>,
+    current: Vec<(u8, u8, u8)>,
+    arrived: Instant,
+    /// Промежуток между двумя последними кадрами: за него вывод проходит путь от
+    /// предыдущего кадра к текущему
+    interval: Duration,
+}
+
+impl Interpolator {
+    fn new() -> Self {
+        Interpolator { previous: Vec::new(), current: Vec::new(), arrived: Instant::now(), interval: Duration::ZERO }
+    }
+
+    fn push(&mut self, colors: Vec<(u8, u8, u8)>) {
+        self.interval = self.arrived.elapsed();
+        self.arrived = Instant::now();
+        self.previous = std::mem::replace(&mut self.current, colors);
+    }
+
+    /// Кадр для текущего момента; `None`, пока не пришло ни одного кадра.
+    fn frame(&self, linear: bool) -> Option<Vec<(u8, u8, u8)>> {
+        if self.current.is_empty() {
+            return None;
+        }
+        let t = if self.interval.is_zero() {
+            1.0
+        } else {
+            self.arrived.elapsed().as_secs_f32() / self.interval.as_secs_f32()
+        };
+        Some(smoothing::interpolate(&self.previous, &self.current, t, linear))
+    }
+}
+
+/// Что главный цикл передал потоку вывода и тот ещё не забрал. Кадр хранится только
+/// последний: если вывод не успевает, устаревший кадр заменяется свежим, а не ждёт
+/// в очереди, и лента не отстаёт от экрана больше чем на один кадр.
+#[derive(Default)]
+struct Inbox {
+    frame: Option<Vec<(u8, u8, u8)>>,
+    config: Option<Box<AmbilightConfig>>,
+    /// Погасить ленту и не повторять последний кадр до следующего
+    blank: bool,
+    /// Главный цикл закончил работу
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Mailbox {
+    inbox: Mutex<Inbox>,
+    ready: Condvar,
+}
+
+/// Счётчики потока вывода, которые читает главный цикл.
+#[derive(Default)]
+pub struct WriterStats {
+    /// Кадров, ушедших на ленту
+    pub sent: AtomicUsize,
+    /// Кадров, отброшенных, потому что поток вывода не успевал
+    pub dropped: AtomicUsize,
+    /// Последняя отправка закончилась ошибкой
+    pub error: AtomicBool,
+}
+
+/// Всё, чем владеет поток вывода.
+struct OutputState {
+    config: AmbilightConfig,
+    layout: ChannelLayout,
+    /// `None`, пока порт потерян и идёт переподключение
+    port: Option<Box<dyn Write + Send>>,
+    /// Светодиодов в последнем кадре (до первого — из настроек): для сверки с прошивкой
+    /// при переоткрытии порта и для гашения ленты
+    led_count: usize,
+    reconnect_attempt: Instant,
+    msg_buffer: Vec<u8>,
+    /// Цвета в физическом порядке ленты при `led_remap`
+    remapped: Vec<(u8, u8, u8)>,
+    delta_encoder: DeltaEncoder,
+    rate_limiter: Option<TokenBucket>,
+    /// Кадр, которому не хватило токенов. Уходит в `retry_at`, когда они накопятся,
+    /// если раньше его не заменит более свежий
+    pending: Option<Vec<(u8, u8, u8)>>,
+    retry_at: Instant,
+    rate_limited_frames: usize,
+    /// Кадров, не записанных за `serial_timeout_ms`
+    timed_out_frames: usize,
+    /// Последний отправленный кадр для `skip_unchanged_writes`; `None` — следующий кадр
+    /// отправляется в любом случае
+    last_sent: Option<Vec<(u8, u8, u8)>>,
+    last_sent_at: Instant,
+    unchanged_frames: usize,
+    stats_timer: Instant,
+    stats: Arc<WriterStats>,
+}
+
+impl OutputState {
+    fn reconfigure(&mut self, config: AmbilightConfig) {
+        if output_changed(&self.config, &config) {
+            match open_output(&config, self.led_count) {
+                Ok(new_port) => {
+                    self.port = Some(new_port);
+                    self.delta_encoder = DeltaEncoder::default();
+                }
+                Err(e) => error!("Не удалось открыть порт {}: {}", config.port_name, e),
+            }
+        }
+        self.rate_limiter = config.max_bytes_per_second.map(TokenBucket::new);
+        // Настройки уже проверены при чтении
+        self.layout = config.channel_layout().unwrap_or_default();
+        self.last_sent = None;
+        self.config = config;
+    }
+
+    fn write_frame(&mut self, frame: &[(u8, u8, u8)]) {
+        self.led_count = frame.len();
+        self.pending = None;
+        let config = &self.config;
+        let colors = if config.led_remap.is_empty() {
+            frame
+        } else {
+            remap::apply_remap(frame, &config.led_remap, &mut self.remapped);
+            &self.remapped
+        };
+        // 7. Формирование пакета Adalight
+        build_packet(config, colors, self.layout, &self.delta_encoder, &mut self.msg_buffer);
+
+        // Порт потерян (например, выдернут USB-кабель): пробуем открыть его заново,
+        // а кадры тем временем продолжают считаться
+        if self.port.is_none() && self.reconnect_attempt.elapsed() >= RECONNECT_INTERVAL {
+            self.reconnect_attempt = Instant::now();
+            match open_output(config, colors.len()) {
+                Ok(new_port) => {
+                    info!("Переподключено к {}", config.port_name);
+                    self.port = Some(new_port);
+                    // Прошивка могла перезагрузиться, поэтому следующий кадр — полный
+                    self.delta_encoder = DeltaEncoder::default();
+                    self.last_sent = None;
+                }
+                Err(e) => debug!("Переподключение к {} не удалось: {}", config.port_name, e),
+            }
+        }
+
+        let unchanged = config.skip_unchanged_writes
+            && self.last_sent_at.elapsed() < UNCHANGED_RESEND_INTERVAL
+            && self.last_sent.as_ref().is_some_and(|sent| colors_match(sent, colors, config.unchanged_write_tolerance));
+        if unchanged {
+            self.unchanged_frames += 1;
+        } else if let Some(output) = &mut self.port {
+            let msg_buffer = &self.msg_buffer;
+            if self.rate_limiter.as_mut().is_none_or(|limiter| limiter.try_consume(msg_buffer.len())) {
+                let mut write_result = output.write_all(msg_buffer);
+                if config.flush_after_write {
+                    write_result = write_result.and_then(|()| output.flush());
+                }
+                self.stats.error.store(write_result.is_err(), Ordering::Relaxed);
+                match write_result {
+                    Ok(()) => {
+                        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                        debug!("Отправлен пакет: {} байт", msg_buffer.len());
+                        if config.delta_packets && config.output.mode == OutputMode::Serial {
+                            self.delta_encoder.mark_sent(colors, msg_buffer);
+                        }
+                        if config.skip_unchanged_writes {
+                            self.last_sent = Some(colors.to_vec());
+                            self.last_sent_at = Instant::now();
+                        }
+                    }
+                    // Таймаут — порт занят, а не потерян: кадр пропускается, порт не переоткрывается
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        self.timed_out_frames += 1;
+                        debug!("Таймаут отправки: {}", e);
+                    }
+                    Err(e) => {
+                        error!("Ошибка отправки: {}", e);
+                        info!("Переподключение к {}…", config.port_name);
+                        self.port = None;
+                        self.reconnect_attempt = Instant::now();
+                    }
+                }
+            } else if let Some(limiter) = &self.rate_limiter {
+                self.rate_limited_frames += 1;
+                self.pending = Some(frame.to_vec());
+                self.retry_at = Instant::now() + limiter.time_until(msg_buffer.len());
+            }
+        }
+
+        if self.stats_timer.elapsed() >= Duration::from_secs(1) {
+            if config.delta_packets {
+                info!("Экономия трафика: {:.1}%", self.delta_encoder.take_savings_percent());
+            }
+            if config.max_bytes_per_second.is_some() {
+                info!("Задержано ограничителем скорости: {}", self.rate_limited_frames);
+            }
+            if config.skip_unchanged_writes {
+                debug!("Не отправлено неизменных кадров: {}", self.unchanged_frames);
+            }
+            if self.timed_out_frames > 0 {
+                warn!(
+                    "Не записано за {} мс: {} кадров. Возможно, стоит увеличить serial_timeout_ms",
+                    config.serial_timeout_ms, self.timed_out_frames
+                );
+            }
+            self.rate_limited_frames = 0;
+            self.timed_out_frames = 0;
+            self.unchanged_frames = 0;
+            self.stats_timer = Instant::now();
+        }
+    }
+
+    /// Гасит ленту полным кадром (без дельта-кодирования и ограничителя скорости).
+    fn blank(&mut self, led_count: usize) {
+        let black = vec![(0, 0, 0); led_count];
+        if self.config.output.mode == OutputMode::Serial {
+            packet::build_adalight_packet_with(&black, self.layout, &mut self.msg_buffer);
+        } else {
+            build_packet(&self.config, &black, self.layout, &self.delta_encoder, &mut self.msg_buffer);
+        }
+        if let Some(port) = &mut self.port
+            && let Err(e) = port.write_all(&self.msg_buffer).and_then(|()| port.flush())
+        {
+            error!("Ошибка отправки: {}", e);
+        }
+    }
+
+    /// Отправляет самый свежий кадр, пока главный цикл не закончит работу.
+    /// При `output_fps` кадры уходят по своему таймеру, а между кадрами главного цикла
+    /// достраиваются промежуточные.
+    fn run(mut self, mailbox: &Mailbox) {
+        let mut interpolator = Interpolator::new();
+        let mut next_output = Instant::now();
+        loop {
+            let output_interval = self.config.output_interval();
+            // Ждём нового кадра, но не дольше следующего кадра по таймеру `output_fps`
+            // и момента, когда отложенному кадру хватит токенов
+            let retry_at = self.pending.is_some().then_some(self.retry_at);
+            let deadline = match (output_interval.map(|_| next_output), retry_at) {
+                (Some(output_at), Some(retry_at)) => Some(output_at.min(retry_at)),
+                (output_at, retry_at) => output_at.or(retry_at),
+            };
+            let (latest, config, blank, closed) = {
+                let mut inbox = mailbox.inbox.lock().unwrap();
+                while inbox.frame.is_none() && inbox.config.is_none() && !inbox.blank && !inbox.closed {
+                    inbox = match deadline {
+                        Some(deadline) => {
+                            let timeout = deadline.saturating_duration_since(Instant::now());
+                            if timeout.is_zero() {
+                                break;
+                            }
+                            mailbox.ready.wait_timeout(inbox, timeout).unwrap().0
+                        }
+                        None => mailbox.ready.wait(inbox).unwrap(),
+                    };
+                }
+                (inbox.frame.take(), inbox.config.take(), std::mem::take(&mut inbox.blank), inbox.closed)
+            };
+            if let Some(config) = config {
+                self.reconfigure(*config);
+            }
+            // Кадр, пришедший до просьбы погасить ленту, уже не нужен
+            let latest = if blank {
+                interpolator = Interpolator::new();
+                self.blank(self.led_count);
+                // Лента погашена полным кадром: дельта-пакеты после паузы считаются заново
+                self.delta_encoder = DeltaEncoder::default();
+                self.last_sent = None;
+                self.pending = None;
+                None
+            } else {
+                latest
+            };
+            // Свежего кадра нет, а отложенному уже хватает токенов
+            if latest.is_none()
+                && Instant::now() >= self.retry_at
+                && let Some(colors) = self.pending.take()
+            {
+                self.write_frame(&colors);
+            }
+
+            let Some(interval) = output_interval else {
+                if let Some(colors) = latest {
+                    self.write_frame(&colors);
+                }
+                if closed {
+                    break;
+                }
+                continue;
+            };
+            if let Some(colors) = latest {
+                interpolator.push(colors);
+            }
+            if Instant::now() >= next_output {
+                // После долгой записи таймер не догоняет пропущенные кадры
+                next_output = (next_output + interval).max(Instant::now());
+                if let Some(colors) = interpolator.frame(self.config.linear_smoothing) {
+                    self.write_frame(&colors);
+                }
+            }
+            if closed {
+                break;
+            }
+        }
+        // Гасим ленту перед выходом, иначе она останется гореть последним кадром
+        self.blank(self.led_count);
+    }
+}
+
+/// Поток, который отправляет кадры на ленту, чтобы медленный порт не задерживал захват.
+pub struct Writer {
+    mailbox: Arc<Mailbox>,
+    thread: JoinHandle<()>,
+    pub stats: Arc<WriterStats>,
+}
+
+impl Writer {
+    /// Запускает поток вывода для ленты из `led_count` светодиодов: столько гасится,
+    /// если погасить ленту до первого кадра.
+    pub fn spawn(config: &AmbilightConfig, port: Box<dyn Write + Send>, led_count: usize) -> io::Result<Self> {
+        let mailbox = Arc::new(Mailbox::default());
+        let stats = Arc::new(WriterStats::default());
+        let state = OutputState {
+            config: config.clone(),
+            layout: config.channel_layout().unwrap_or_default(),
+            port: Some(port),
+            led_count,
+            reconnect_attempt: Instant::now(),
+            msg_buffer: Vec::new(),
+            remapped: Vec::new(),
+            delta_encoder: DeltaEncoder::default(),
+            rate_limiter: config.max_bytes_per_second.map(TokenBucket::new),
+            pending: None,
+            retry_at: Instant::now(),
+            rate_limited_frames: 0,
+            timed_out_frames: 0,
+            last_sent: None,
+            last_sent_at: Instant::now(),
+            unchanged_frames: 0,
+            stats_timer: Instant::now(),
+            stats: Arc::clone(&stats),
+        };
+        let thread = thread::Builder::new()
+            .name("output".to_string())
+            .spawn({
+                let mailbox = Arc::clone(&mailbox);
+                move || state.run(&mailbox)
+            })?;
+        Ok(Writer { mailbox, thread, stats })
+    }
+
+    /// Передаёт кадр потоку вывода. Если тот ещё не забрал предыдущий, предыдущий
+    /// отбрасывается: на ленту всегда уходит самый свежий кадр.
+    pub fn send(&self, colors: Vec<(u8, u8, u8)>) {
+        if self.mailbox.inbox.lock().unwrap().frame.replace(colors).is_some() {
+            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.mailbox.ready.notify_one();
+    }
+
+    /// Гасит ленту тем же пакетом, что и при выходе. Последний кадр после этого
+    /// не повторяется, пока не придёт новый.
+    pub fn blank(&self) {
+        self.mailbox.inbox.lock().unwrap().blank = true;
+        self.mailbox.ready.notify_one();
+    }
+
+    /// Передаёт потоку вывода перечитанные настройки; порт переоткрывается, если он изменился.
+    pub fn reconfigure(&self, config: &AmbilightConfig) {
+        self.mailbox.inbox.lock().unwrap().config = Some(Box::new(config.clone()));
+        self.mailbox.ready.notify_one();
+    }
+
+    /// Дожидается отправки последнего кадра и гашения ленты.
+    pub fn stop(self) {
+        self.mailbox.inbox.lock().unwrap().closed = true;
+        self.mailbox.ready.notify_one();
+        if self.thread.join().is_err() {
+            error!("Поток вывода завершился с ошибкой");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CONFIG;
+
+    /// Порт, который запоминает всё записанное.
+    #[derive(Clone, Default)]
+    struct RecordingPort(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for RecordingPort {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn blank_before_first_frame_sends_full_black_frame() {
+        let config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        let port = RecordingPort::default();
+        let writer = Writer::spawn(&config, Box::new(port.clone()), 3).unwrap();
+        writer.blank();
+        writer.stop();
+
+        let mut black = Vec::new();
+        packet::build_adalight_packet_with(&[(0, 0, 0); 3], config.channel_layout().unwrap(), &mut black);
+        // Гашение по просьбе и перед выходом
+        assert_eq!(*port.0.lock().unwrap(), [black.clone(), black].concat());
+    }
+
+    #[test]
+    fn rate_limited_frame_is_sent_when_tokens_refill() {
+        let mut config: AmbilightConfig = toml::from_str(DEFAULT_CONFIG).unwrap();
+        // 250 байт в ведре, пакет на 50 светодиодов — 156 байт: второй ждёт около 60 мс
+        config.max_bytes_per_second = Some(1000);
+        let port = RecordingPort::default();
+        let writer = Writer::spawn(&config, Box::new(port.clone()), 50).unwrap();
+        let (first, second) = (vec![(1, 2, 3); 50], vec![(4, 5, 6); 50]);
+        writer.send(first.clone());
+        thread::sleep(Duration::from_millis(50));
+        writer.send(second.clone());
+        thread::sleep(Duration::from_millis(500));
+
+        let layout = config.channel_layout().unwrap();
+        let (mut first_packet, mut second_packet) = (Vec::new(), Vec::new());
+        packet::build_adalight_packet_with(&first, layout, &mut first_packet);
+        packet::build_adalight_packet_with(&second, layout, &mut second_packet);
+        // Второй кадр ушёл, хотя новых кадров больше не было
+        assert_eq!(*port.0.lock().unwrap(), [first_packet, second_packet].concat());
+        writer.stop();
+    }
+
+    #[test]
+    fn colors_match_within_tolerance() {
+        let sent = [(10, 20, 30), (200, 200, 200)];
+        assert!(colors_match(&sent, &sent, 0));
+        assert!(!colors_match(&sent, &[(11, 20, 30), (200, 200, 200)], 0));
+        assert!(colors_match(&sent, &[(12, 18, 30), (200, 201, 199)], 2));
+        assert!(!colors_match(&sent, &[(13, 20, 30), (200, 200, 200)], 2));
+        // Другое число светодиодов — всегда новый кадр
+        assert!(!colors_match(&sent, &sent[..1], 255));
+    }
+
+    #[test]
+    fn led_count_is_read_from_firmware_banner() {
+        assert_eq!(banner_led_count(b"Ada\n"), None);
+        assert_eq!(banner_led_count(b"Ada\nLEDs: 120\n"), Some(120));
+        assert_eq!(banner_led_count(b"Ada 98\r\n"), Some(98));
+        // Строка ещё не дошла целиком
+        assert_eq!(banner_led_count(b"Ada 12"), None);
+    }
+}